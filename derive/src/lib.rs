@@ -0,0 +1,386 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Derive macros of `greptimedb-ingester`, use them through the `derive`
+//! feature of that crate rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Field, Fields, GenericArgument, Ident, LitStr, PathArguments, Type};
+
+/// Derive `greptimedb_ingester::helpers::row::GreptimeRow` for a struct with
+/// named fields, each field becoming a column.
+///
+/// Field attributes:
+///
+/// - `#[greptime(tag)]`: a tag column
+/// - `#[greptime(field)]`: a field column, the default for fields without
+///   attribute
+/// - `#[greptime(timestamp = "ms")]`: the timestamp column of an `i64` field,
+///   with unit `s`, `ms`, `us` or `ns`. Exactly one is required.
+/// - `#[greptime(name = "column")]`: column name, defaults to the field name
+///
+/// Supported field types are the integer and float primitives, `bool`,
+/// `String`, `Vec<u8>` and `Option` of them, where `None` is written as null.
+#[proc_macro_derive(GreptimeRow, attributes(greptime))]
+pub fn derive_greptime_row(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Semantic {
+    Tag,
+    Field,
+    Timestamp(TimeUnit),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeUnit {
+    Second,
+    Millisecond,
+    Microsecond,
+    Nanosecond,
+}
+
+impl TimeUnit {
+    fn parse(unit: &LitStr) -> syn::Result<Self> {
+        match unit.value().as_str() {
+            "s" => Ok(TimeUnit::Second),
+            "ms" => Ok(TimeUnit::Millisecond),
+            "us" => Ok(TimeUnit::Microsecond),
+            "ns" => Ok(TimeUnit::Nanosecond),
+            _ => Err(syn::Error::new(
+                unit.span(),
+                "unsupported timestamp unit, expect one of \"s\", \"ms\", \"us\", \"ns\"",
+            )),
+        }
+    }
+
+    /// The `ColumnDataType` variant and the `helpers::values` function.
+    fn mapping(&self) -> (&'static str, &'static str) {
+        match self {
+            TimeUnit::Second => ("TimestampSecond", "timestamp_second_value"),
+            TimeUnit::Millisecond => ("TimestampMillisecond", "timestamp_millisecond_value"),
+            TimeUnit::Microsecond => ("TimestampMicrosecond", "timestamp_microsecond_value"),
+            TimeUnit::Nanosecond => ("TimestampNanosecond", "timestamp_nanosecond_value"),
+        }
+    }
+}
+
+struct Column {
+    ident: Ident,
+    name: String,
+    semantic: Semantic,
+    nullable: bool,
+    datatype: &'static str,
+    value_fn: &'static str,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "GreptimeRow can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "GreptimeRow can only be derived for structs with named fields",
+        ));
+    };
+
+    let columns = fields
+        .named
+        .iter()
+        .map(parse_column)
+        .collect::<syn::Result<Vec<_>>>()?;
+    let timestamps = columns
+        .iter()
+        .filter(|c| matches!(c.semantic, Semantic::Timestamp(_)))
+        .count();
+    if timestamps != 1 {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "GreptimeRow requires exactly one #[greptime(timestamp = \"<unit>\")] field",
+        ));
+    }
+
+    let schema = columns.iter().map(|c| {
+        let name = &c.name;
+        let datatype = Ident::new(c.datatype, Span::call_site());
+        let schema_fn = match c.semantic {
+            Semantic::Tag => quote!(tag),
+            Semantic::Field => quote!(field),
+            Semantic::Timestamp(_) => quote!(timestamp),
+        };
+        quote! {
+            ::greptimedb_ingester::helpers::schema::#schema_fn(
+                #name,
+                ::greptimedb_ingester::api::v1::ColumnDataType::#datatype,
+            )
+        }
+    });
+
+    let values = columns.iter().map(|c| {
+        let ident = &c.ident;
+        let value_fn = Ident::new(c.value_fn, Span::call_site());
+        if c.nullable {
+            quote! {
+                match self.#ident {
+                    ::std::option::Option::Some(v) => {
+                        ::greptimedb_ingester::helpers::values::#value_fn(v)
+                    }
+                    ::std::option::Option::None => {
+                        ::greptimedb_ingester::helpers::values::none_value()
+                    }
+                }
+            }
+        } else {
+            quote!(::greptimedb_ingester::helpers::values::#value_fn(self.#ident))
+        }
+    });
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::greptimedb_ingester::helpers::row::GreptimeRow
+            for #ident #ty_generics #where_clause
+        {
+            fn schema() -> ::std::vec::Vec<::greptimedb_ingester::api::v1::ColumnSchema> {
+                ::std::vec![#(#schema),*]
+            }
+
+            fn into_row(self) -> ::greptimedb_ingester::api::v1::Row {
+                ::greptimedb_ingester::api::v1::Row {
+                    values: ::std::vec![#(#values),*],
+                }
+            }
+        }
+    })
+}
+
+fn parse_column(field: &Field) -> syn::Result<Column> {
+    let ident = field.ident.clone().expect("named field");
+    let mut name = ident.unraw().to_string();
+    let mut semantic = None;
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("greptime")) {
+        attr.parse_nested_meta(|meta| {
+            let parsed = if meta.path.is_ident("tag") {
+                Semantic::Tag
+            } else if meta.path.is_ident("field") {
+                Semantic::Field
+            } else if meta.path.is_ident("timestamp") {
+                let unit: LitStr = meta.value()?.parse()?;
+                Semantic::Timestamp(TimeUnit::parse(&unit)?)
+            } else if meta.path.is_ident("name") {
+                let lit: LitStr = meta.value()?.parse()?;
+                name = lit.value();
+                return Ok(());
+            } else {
+                return Err(meta.error("unsupported greptime attribute"));
+            };
+
+            if semantic.replace(parsed).is_some() {
+                return Err(meta.error("only one of tag, field and timestamp is allowed"));
+            }
+            Ok(())
+        })?;
+    }
+    let semantic = semantic.unwrap_or(Semantic::Field);
+
+    let (inner_ty, nullable) = match option_inner(&field.ty) {
+        Some(inner) => (inner, true),
+        None => (&field.ty, false),
+    };
+    let (datatype, value_fn) = match semantic {
+        Semantic::Timestamp(unit) => {
+            if nullable || type_name(inner_ty).as_deref() != Some("i64") {
+                return Err(syn::Error::new(
+                    field.ty.span(),
+                    "timestamp field must be of type i64",
+                ));
+            }
+            unit.mapping()
+        }
+        Semantic::Tag | Semantic::Field => scalar_mapping(inner_ty).ok_or_else(|| {
+            syn::Error::new(
+                inner_ty.span(),
+                "unsupported field type for GreptimeRow, expect integer, float, bool, \
+                 String, Vec<u8> or Option of them",
+            )
+        })?,
+    };
+
+    Ok(Column {
+        ident,
+        name,
+        semantic,
+        nullable,
+        datatype,
+        value_fn,
+    })
+}
+
+/// The `ColumnDataType` variant and the `helpers::values` function of a type.
+fn scalar_mapping(ty: &Type) -> Option<(&'static str, &'static str)> {
+    let mapping = match type_name(ty)?.as_str() {
+        "i8" => ("Int8", "i8_value"),
+        "i16" => ("Int16", "i16_value"),
+        "i32" => ("Int32", "i32_value"),
+        "i64" => ("Int64", "i64_value"),
+        "u8" => ("Uint8", "u8_value"),
+        "u16" => ("Uint16", "u16_value"),
+        "u32" => ("Uint32", "u32_value"),
+        "u64" => ("Uint64", "u64_value"),
+        "f32" => ("Float32", "f32_value"),
+        "f64" => ("Float64", "f64_value"),
+        "bool" => ("Boolean", "bool_value"),
+        "String" => ("String", "string_value"),
+        "Vec" if generic_arg(ty).and_then(type_name).as_deref() == Some("u8") => {
+            ("Binary", "binary_value")
+        }
+        _ => return None,
+    };
+    Some(mapping)
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    if type_name(ty).as_deref() == Some("Option") {
+        generic_arg(ty)
+    } else {
+        None
+    }
+}
+
+/// Last path segment of a type, e.g. `Option` of `std::option::Option<T>`.
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            path.path.segments.last().map(|s| s.ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// The single generic type argument of a type, e.g. `T` of `Option<T>`.
+fn generic_arg(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let PathArguments::AngleBracketed(args) = &path.path.segments.last()?.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(ty) if args.args.len() == 1 => Some(ty),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn test_scalar_mapping() {
+        let ty: Type = parse_quote!(i32);
+        assert_eq!(Some(("Int32", "i32_value")), scalar_mapping(&ty));
+        let ty: Type = parse_quote!(std::string::String);
+        assert_eq!(Some(("String", "string_value")), scalar_mapping(&ty));
+        let ty: Type = parse_quote!(Vec<u8>);
+        assert_eq!(Some(("Binary", "binary_value")), scalar_mapping(&ty));
+
+        let ty: Type = parse_quote!(Vec<i32>);
+        assert_eq!(None, scalar_mapping(&ty));
+        let ty: Type = parse_quote!(&str);
+        assert_eq!(None, scalar_mapping(&ty));
+        let ty: Type = parse_quote!(HashMap<String, String>);
+        assert_eq!(None, scalar_mapping(&ty));
+    }
+
+    #[test]
+    fn test_option_inner() {
+        let ty: Type = parse_quote!(Option<f64>);
+        let inner = option_inner(&ty).unwrap();
+        assert_eq!(Some("f64".to_string()), type_name(inner));
+
+        let ty: Type = parse_quote!(f64);
+        assert!(option_inner(&ty).is_none());
+    }
+
+    #[test]
+    fn test_parse_column() {
+        let field: Field = parse_quote!(#[greptime(tag, name = "host_name")] host: String);
+        let column = parse_column(&field).unwrap();
+        assert_eq!("host_name", column.name);
+        assert_eq!(Semantic::Tag, column.semantic);
+        assert!(!column.nullable);
+
+        let field: Field = parse_quote!(#[greptime(timestamp = "ns")] ts: i64);
+        let column = parse_column(&field).unwrap();
+        assert_eq!(Semantic::Timestamp(TimeUnit::Nanosecond), column.semantic);
+        assert_eq!("TimestampNanosecond", column.datatype);
+
+        let field: Field = parse_quote!(cpu: Option<f64>);
+        let column = parse_column(&field).unwrap();
+        assert_eq!(Semantic::Field, column.semantic);
+        assert!(column.nullable);
+
+        let field: Field = parse_quote!(#[greptime(timestamp = "ms")] ts: String);
+        assert!(parse_column(&field).is_err());
+        let field: Field = parse_quote!(#[greptime(timestamp = "min")] ts: i64);
+        assert!(parse_column(&field).is_err());
+        let field: Field = parse_quote!(#[greptime(tag, field)] host: String);
+        assert!(parse_column(&field).is_err());
+        let field: Field = parse_quote!(#[greptime(index)] host: String);
+        assert!(parse_column(&field).is_err());
+    }
+
+    #[test]
+    fn test_expand() {
+        let input: DeriveInput = parse_quote! {
+            struct Cpu {
+                #[greptime(tag)]
+                host: String,
+                #[greptime(timestamp = "ms")]
+                ts: i64,
+                usage: Option<f64>,
+            }
+        };
+        assert!(expand(input).is_ok());
+
+        let input: DeriveInput = parse_quote! {
+            struct Cpu {
+                #[greptime(tag)]
+                host: String,
+            }
+        };
+        assert!(expand(input).is_err());
+
+        let input: DeriveInput = parse_quote! {
+            struct Cpu(String, i64);
+        };
+        assert!(expand(input).is_err());
+    }
+}
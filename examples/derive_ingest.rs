@@ -0,0 +1,83 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use greptimedb_ingester::api::v1::*;
+use greptimedb_ingester::helpers::row::to_insert_request;
+use greptimedb_ingester::{ClientBuilder, Database, GreptimeRow, DEFAULT_SCHEMA_NAME};
+
+#[tokio::main]
+async fn main() {
+    let greptimedb_endpoint =
+        std::env::var("GREPTIMEDB_ENDPOINT").unwrap_or_else(|_| "localhost:4001".to_owned());
+    let greptimedb_dbname =
+        std::env::var("GREPTIMEDB_DBNAME").unwrap_or_else(|_| DEFAULT_SCHEMA_NAME.to_owned());
+
+    let grpc_client = ClientBuilder::default()
+        .peers(vec![&greptimedb_endpoint])
+        .build();
+    let client = Database::new_with_dbname(greptimedb_dbname, grpc_client);
+
+    let requests = RowInsertRequests {
+        inserts: vec![to_insert_request("weather_demo", weather_records())],
+    };
+    match client.row_insert(requests).await {
+        Ok(rows) => {
+            println!("Rows written: {rows}");
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+        }
+    };
+}
+
+/// Schema and rows of the `weather_demo` table are derived from the struct:
+///
+/// - `ts`: a timestamp column
+/// - `collector`: a tag column
+/// - `temperature`: a value field of f32
+/// - `humidity`: a nullable value field of i32
+#[derive(GreptimeRow)]
+struct WeatherRecord {
+    #[greptime(timestamp = "ms")]
+    ts: i64,
+    #[greptime(tag)]
+    collector: String,
+    #[greptime(field)]
+    temperature: f32,
+    #[greptime(field)]
+    humidity: Option<i32>,
+}
+
+fn weather_records() -> Vec<WeatherRecord> {
+    vec![
+        WeatherRecord {
+            ts: 1686109527000,
+            collector: "c1".to_owned(),
+            temperature: 26.4,
+            humidity: Some(15),
+        },
+        WeatherRecord {
+            ts: 1686023127000,
+            collector: "c1".to_owned(),
+            temperature: 29.3,
+            humidity: None,
+        },
+        WeatherRecord {
+            ts: 1686109527000,
+            collector: "c2".to_owned(),
+            temperature: 20.4,
+            humidity: Some(67),
+        },
+    ]
+}
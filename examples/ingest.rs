@@ -12,13 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use derive_new::new;
 
 use greptimedb_ingester::api::v1::*;
 use greptimedb_ingester::helpers::schema::*;
 use greptimedb_ingester::helpers::values::*;
 use greptimedb_ingester::{
-    ChannelConfig, ChannelManager, ClientBuilder, ClientTlsOption, Database, DEFAULT_SCHEMA_NAME,
+    ChannelConfig, ChannelManager, ClientBuilder, ClientTlsOption, Database, Hints,
+    DEFAULT_SCHEMA_NAME,
 };
 
 #[tokio::main]
@@ -47,8 +50,9 @@ async fn main() {
     let client = Database::new_with_dbname(greptimedb_dbname, grpc_client);
 
     let records = weather_records();
+    let hints = Hints::new().ttl(Duration::from_secs(24 * 3600));
     let result = client
-        .row_insert_with_hint(to_insert_requests(records), "ttl=1d")
+        .row_insert_with_hints(to_insert_requests(records), &hints)
         .await;
     match result {
         Ok(rows) => {
@@ -0,0 +1,426 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use prost::Message;
+use snafu::ResultExt;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::api::v1::{ColumnSchema, Row, RowInsertRequest, RowInsertRequests, Rows};
+use crate::error::{self, Result};
+use crate::{Database, Hints};
+
+const DEFAULT_MAX_ROWS: usize = 4096;
+const DEFAULT_MAX_BYTES: usize = 4 * 1024 * 1024;
+const DEFAULT_LINGER: Duration = Duration::from_millis(100);
+const DEFAULT_CHANNEL_SIZE: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BulkWriterOptions {
+    pub max_rows: usize,
+    pub max_bytes: usize,
+    pub linger: Duration,
+    pub channel_size: usize,
+    pub hints: Hints,
+}
+
+impl Default for BulkWriterOptions {
+    fn default() -> Self {
+        Self {
+            max_rows: DEFAULT_MAX_ROWS,
+            max_bytes: DEFAULT_MAX_BYTES,
+            linger: DEFAULT_LINGER,
+            channel_size: DEFAULT_CHANNEL_SIZE,
+            hints: Hints::default(),
+        }
+    }
+}
+
+impl BulkWriterOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Flush once this many rows are buffered across all tables.
+    ///
+    /// Default is 4096
+    pub fn max_rows(self, max_rows: usize) -> Self {
+        Self { max_rows, ..self }
+    }
+
+    /// Flush once the buffered rows are estimated to exceed this many bytes
+    /// when encoded.
+    ///
+    /// Default is 4MiB
+    pub fn max_bytes(self, max_bytes: usize) -> Self {
+        Self { max_bytes, ..self }
+    }
+
+    /// Flush buffered rows at latest this long after the first of them was
+    /// written.
+    ///
+    /// Default is 100ms
+    pub fn linger(self, linger: Duration) -> Self {
+        Self { linger, ..self }
+    }
+
+    /// Size of the channel between the writer handle and the background task.
+    ///
+    /// Default is 1024
+    pub fn channel_size(self, channel_size: usize) -> Self {
+        Self {
+            channel_size,
+            ..self
+        }
+    }
+
+    /// Hints sent with every flush.
+    pub fn hints(self, hints: Hints) -> Self {
+        Self { hints, ..self }
+    }
+}
+
+/// Outcome of a single flush performed by a [`BulkWriter`].
+#[derive(Debug)]
+pub struct FlushResult {
+    /// Number of rows sent in this flush
+    pub rows: usize,
+    /// Rows written reported by the server, or the error of the flush
+    pub result: Result<u32>,
+}
+
+/// Receiving end of the [`FlushResult`]s produced by a [`BulkWriter`].
+///
+/// Dropping it is fine, flush results are then discarded.
+#[derive(Debug)]
+pub struct FlushResults {
+    receiver: mpsc::UnboundedReceiver<FlushResult>,
+}
+
+impl FlushResults {
+    /// Wait for the next flush result. Returns `None` once the writer is
+    /// finished and all results have been received.
+    pub async fn next(&mut self) -> Option<FlushResult> {
+        self.receiver.recv().await
+    }
+
+    /// Take a flush result if one is ready, without waiting.
+    pub fn try_next(&mut self) -> Option<FlushResult> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+enum Command {
+    Write {
+        table_name: String,
+        schema: Vec<ColumnSchema>,
+        rows: Vec<Row>,
+    },
+    Flush(oneshot::Sender<()>),
+}
+
+/// A writer that batches rows of any number of tables into
+/// [`RowInsertRequests`] and sends them with [`Database::row_insert`].
+///
+/// Buffered rows are flushed when `max_rows` or `max_bytes` is reached, or
+/// when the oldest buffered row has waited for `linger`. You can obtain a
+/// [`BulkWriter`] via [`Database::bulk_writer`].
+///
+/// ```ignore
+/// let client = Database::new_with_dbname("db_name", grpc_client);
+/// let (writer, mut results) = client.bulk_writer(BulkWriterOptions::default());
+/// writer.write_row("weather", &schema, row).await?;
+/// let total = writer.finish().await?;
+/// ```
+pub struct BulkWriter {
+    sender: mpsc::Sender<Command>,
+
+    join: JoinHandle<u32>,
+}
+
+impl BulkWriter {
+    pub(crate) fn new(database: Database, options: BulkWriterOptions) -> (Self, FlushResults) {
+        let (sender, receiver) = mpsc::channel(options.channel_size);
+        let (result_sender, result_receiver) = mpsc::unbounded_channel();
+
+        let join = tokio::spawn(run(database, options, receiver, result_sender));
+
+        (
+            BulkWriter { sender, join },
+            FlushResults {
+                receiver: result_receiver,
+            },
+        )
+    }
+
+    /// Buffer a single row of `table_name`.
+    pub async fn write_row(
+        &self,
+        table_name: impl Into<String>,
+        schema: &[ColumnSchema],
+        row: Row,
+    ) -> Result<()> {
+        self.send(Command::Write {
+            table_name: table_name.into(),
+            schema: schema.to_vec(),
+            rows: vec![row],
+        })
+        .await
+    }
+
+    /// Buffer all rows of `table_name`.
+    pub async fn write_rows(&self, table_name: impl Into<String>, rows: Rows) -> Result<()> {
+        self.send(Command::Write {
+            table_name: table_name.into(),
+            schema: rows.schema,
+            rows: rows.rows,
+        })
+        .await
+    }
+
+    /// Flush all buffered rows and wait until the flush is done.
+    pub async fn flush(&self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.send(Command::Flush(tx)).await?;
+        rx.await.map_err(|_| error::BulkWriterClosedSnafu.build())
+    }
+
+    /// Flush the remaining rows, stop the background task and get the total
+    /// rows written.
+    pub async fn finish(self) -> Result<u32> {
+        drop(self.sender);
+
+        self.join.await.context(error::JoinTaskSnafu)
+    }
+
+    async fn send(&self, command: Command) -> Result<()> {
+        self.sender
+            .send(command)
+            .await
+            .map_err(|_| error::BulkWriterClosedSnafu.build())
+    }
+}
+
+async fn run(
+    database: Database,
+    options: BulkWriterOptions,
+    mut receiver: mpsc::Receiver<Command>,
+    results: mpsc::UnboundedSender<FlushResult>,
+) -> u32 {
+    let mut buffer = Buffer::default();
+    let mut total = 0;
+
+    let linger = tokio::time::sleep(options.linger);
+    tokio::pin!(linger);
+
+    loop {
+        tokio::select! {
+            command = receiver.recv() => match command {
+                Some(Command::Write { table_name, schema, rows }) => {
+                    let was_empty = buffer.is_empty();
+                    let stale = buffer.push(table_name, schema, rows);
+                    if was_empty || stale.is_some() {
+                        linger.as_mut().reset(Instant::now() + options.linger);
+                    }
+                    if let Some(stale) = stale {
+                        total += flush(&database, &options, stale, &results).await;
+                    }
+                    if buffer.is_full(&options) {
+                        total += flush(&database, &options, buffer.take(), &results).await;
+                    }
+                }
+                Some(Command::Flush(done)) => {
+                    total += flush(&database, &options, buffer.take(), &results).await;
+                    let _ = done.send(());
+                }
+                None => {
+                    total += flush(&database, &options, buffer.take(), &results).await;
+                    break;
+                }
+            },
+            _ = &mut linger, if !buffer.is_empty() => {
+                total += flush(&database, &options, buffer.take(), &results).await;
+            }
+        }
+    }
+
+    total
+}
+
+async fn flush(
+    database: &Database,
+    options: &BulkWriterOptions,
+    requests: RowInsertRequests,
+    results: &mpsc::UnboundedSender<FlushResult>,
+) -> u32 {
+    let rows: usize = requests
+        .inserts
+        .iter()
+        .filter_map(|insert| insert.rows.as_ref())
+        .map(|rows| rows.rows.len())
+        .sum();
+    if rows == 0 {
+        return 0;
+    }
+
+    let result = database
+        .row_insert_with_hints(requests, &options.hints)
+        .await;
+    let written = *result.as_ref().unwrap_or(&0);
+
+    // The caller may not care about flush results and have dropped the receiver.
+    let _ = results.send(FlushResult { rows, result });
+
+    written
+}
+
+#[derive(Default)]
+struct Buffer {
+    tables: HashMap<String, Rows>,
+    rows: usize,
+    bytes: usize,
+}
+
+impl Buffer {
+    fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    fn is_full(&self, options: &BulkWriterOptions) -> bool {
+        self.rows >= options.max_rows || self.bytes >= options.max_bytes
+    }
+
+    /// Buffer rows of a table. If the table is already buffered with another
+    /// schema, the rows buffered so far are returned to be flushed first.
+    fn push(
+        &mut self,
+        table_name: String,
+        schema: Vec<ColumnSchema>,
+        rows: Vec<Row>,
+    ) -> Option<RowInsertRequests> {
+        let mut stale = None;
+        if let Some(buffered) = self.tables.get(&table_name) {
+            if buffered.schema != schema {
+                stale = Some(self.take());
+            }
+        }
+
+        self.rows += rows.len();
+        self.bytes += rows.iter().map(Message::encoded_len).sum::<usize>();
+        self.tables
+            .entry(table_name)
+            .or_insert_with(|| Rows {
+                schema,
+                rows: vec![],
+            })
+            .rows
+            .extend(rows);
+
+        stale
+    }
+
+    fn take(&mut self) -> RowInsertRequests {
+        self.rows = 0;
+        self.bytes = 0;
+
+        let inserts = self
+            .tables
+            .drain()
+            .map(|(table_name, rows)| RowInsertRequest {
+                table_name,
+                rows: Some(rows),
+            })
+            .collect();
+        RowInsertRequests { inserts }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::v1::ColumnDataType;
+    use crate::helpers::schema::{field, timestamp};
+    use crate::helpers::values::{i64_value, timestamp_millisecond_value};
+
+    fn mock_row(ts: i64) -> Row {
+        Row {
+            values: vec![timestamp_millisecond_value(ts), i64_value(ts)],
+        }
+    }
+
+    #[test]
+    fn test_buffer_thresholds() {
+        let schema = vec![
+            timestamp("ts", ColumnDataType::TimestampMillisecond),
+            field("value", ColumnDataType::Int64),
+        ];
+        let options = BulkWriterOptions::new().max_rows(3);
+        let mut buffer = Buffer::default();
+        assert!(buffer.is_empty());
+
+        assert!(buffer
+            .push("t1".to_string(), schema.clone(), vec![mock_row(1)])
+            .is_none());
+        assert!(buffer
+            .push("t2".to_string(), schema.clone(), vec![mock_row(2)])
+            .is_none());
+        assert!(!buffer.is_full(&options));
+        assert!(buffer
+            .push("t1".to_string(), schema.clone(), vec![mock_row(3)])
+            .is_none());
+        assert!(buffer.is_full(&options));
+
+        let requests = buffer.take();
+        assert!(buffer.is_empty());
+        assert_eq!(2, requests.inserts.len());
+        let rows: usize = requests
+            .inserts
+            .iter()
+            .map(|insert| insert.rows.as_ref().unwrap().rows.len())
+            .sum();
+        assert_eq!(3, rows);
+
+        let options = BulkWriterOptions::new().max_bytes(1);
+        buffer.push("t1".to_string(), schema, vec![mock_row(4)]);
+        assert!(buffer.is_full(&options));
+    }
+
+    #[test]
+    fn test_buffer_schema_change() {
+        let schema = vec![timestamp("ts", ColumnDataType::TimestampMillisecond)];
+        let mut buffer = Buffer::default();
+        buffer.push(
+            "t1".to_string(),
+            schema.clone(),
+            vec![Row {
+                values: vec![timestamp_millisecond_value(1)],
+            }],
+        );
+
+        let mut new_schema = schema;
+        new_schema.push(field("value", ColumnDataType::Int64));
+        let stale = buffer
+            .push("t1".to_string(), new_schema.clone(), vec![mock_row(2)])
+            .unwrap();
+
+        assert_eq!(1, stale.inserts.len());
+        assert_eq!(1, stale.inserts[0].rows.as_ref().unwrap().rows.len());
+        assert_eq!(1, buffer.rows);
+        assert_eq!(new_schema, buffer.tables["t1"].schema);
+    }
+}
@@ -26,6 +26,8 @@ use tonic::transport::{
 use tower::make::MakeConnection;
 
 use crate::error::{CreateChannelSnafu, InvalidConfigFilePathSnafu, InvalidTlsConfigSnafu, Result};
+use crate::metrics;
+use crate::trace;
 
 const RECYCLE_CHANNEL_INTERVAL_SECS: u64 = 60;
 
@@ -97,6 +99,10 @@ impl ChannelManager {
 
     pub fn get(&self, addr: impl AsRef<str>) -> Result<InnerChannel> {
         let addr = addr.as_ref();
+        trace::get_channel_span(addr).in_scope(|| self.get_or_connect(addr))
+    }
+
+    fn get_or_connect(&self, addr: &str) -> Result<InnerChannel> {
         // It will acquire the read lock.
         if let Some(inner_ch) = self.pool.get(addr) {
             return Ok(inner_ch);
@@ -117,7 +123,10 @@ impl ChannelManager {
                     access: AtomicUsize::new(1),
                     use_default_connector: true,
                 };
-                entry.insert(channel)
+                let channel = entry.insert(channel).channel.clone();
+                // The pool is counted once the lock of the entry is released.
+                metrics::record_channel_pool(self.pool.len(), 0);
+                return Ok(channel);
             }
         };
         Ok(entry.channel.clone())
@@ -426,6 +435,10 @@ impl Pool {
         channel.map(|ch| ch.access())
     }
 
+    fn len(&self) -> usize {
+        self.channels.len()
+    }
+
     fn put(&self, addr: &str, channel: Channel) {
         self.channels.insert(addr.to_string(), channel);
     }
@@ -443,7 +456,10 @@ async fn recycle_channel_in_loop(pool: Arc<Pool>, interval_secs: u64) {
 
     loop {
         interval.tick().await;
-        pool.retain_channel(|_, c| c.access.swap(0, Ordering::Relaxed) != 0)
+        let before = pool.len();
+        pool.retain_channel(|_, c| c.access.swap(0, Ordering::Relaxed) != 0);
+        let after = pool.len();
+        metrics::record_channel_pool(after, before.saturating_sub(after));
     }
 }
 
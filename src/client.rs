@@ -12,27 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use crate::api::v1::greptime_database_client::GreptimeDatabaseClient;
 use crate::api::v1::health_check_client::HealthCheckClient;
 use crate::api::v1::HealthCheckRequest;
 use crate::channel_manager::ChannelManager;
+#[cfg(feature = "flight")]
+use arrow_flight::flight_service_client::FlightServiceClient;
 use parking_lot::RwLock;
 use snafu::OptionExt;
 use tonic::codec::CompressionEncoding;
 use tonic::transport::Channel;
 
+use crate::health::{HealthCheckConfig, HealthTracker, PeerHealth};
 use crate::load_balance::{LoadBalance, Loadbalancer};
+use crate::retry::RetryPolicy;
 use crate::{error, Result};
 use derive_builder::Builder;
 
 const MAX_MESSAGE_SIZE: usize = 512 * 1024 * 1024;
 
 pub(crate) struct DatabaseClient {
+    pub(crate) peer: String,
     pub(crate) inner: GreptimeDatabaseClient<Channel>,
 }
 
+#[cfg(feature = "flight")]
+pub(crate) struct FlightClient {
+    pub(crate) peer: String,
+    pub(crate) inner: FlightServiceClient<Channel>,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Client {
     inner: Arc<Inner>,
@@ -44,6 +57,9 @@ pub struct ClientBuilder {
     load_balance: Loadbalancer,
     compression: Compression,
     peers: Vec<String>,
+    retry_policy: RetryPolicy,
+    health_check: Option<HealthCheckConfig>,
+    max_request_size: Option<usize>,
 }
 
 impl ClientBuilder {
@@ -62,6 +78,20 @@ impl ClientBuilder {
         self
     }
 
+    /// Retry policy of `row_insert`, `delete` and `health_check`. Each retry
+    /// picks a peer through the load balancer again.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Enable tracking the health of peers. Unhealthy peers are left out of
+    /// peer selection until a periodic health check probe succeeds again.
+    pub fn peer_health_check(mut self, config: HealthCheckConfig) -> Self {
+        self.health_check = Some(config);
+        self
+    }
+
     pub fn peers<U, A>(mut self, peers: A) -> Self
     where
         U: AsRef<str>,
@@ -71,21 +101,41 @@ impl ClientBuilder {
         self
     }
 
+    /// Maximum encoded size of a `row_insert` request, larger ones are split
+    /// into several requests. It should not exceed the receive limit of the
+    /// server, `grpc.max_recv_message_size`. Default is 512 MiB.
+    pub fn max_request_size(mut self, max_request_size: usize) -> Self {
+        self.max_request_size = Some(max_request_size);
+        self
+    }
+
+    /// Build the client. The health check probes, if enabled, start at once
+    /// within a tokio runtime, otherwise on the first request.
     pub fn build(self) -> Client {
+        let probe = self
+            .health_check
+            .as_ref()
+            .map(|config| (config.probe_interval, config.probe_timeout));
         let inner = InnerBuilder::default()
             .channel_manager(self.channel_manager)
             .load_balance(self.load_balance)
             .compression(self.compression)
             .peers(self.peers)
+            .retry_policy(self.retry_policy)
+            .max_request_size(self.max_request_size)
+            .health(Arc::new(HealthTracker::new(self.health_check)))
+            .probe(probe)
             .build()
             .unwrap();
-        Client {
+        let client = Client {
             inner: Arc::new(inner),
-        }
+        };
+        client.start_prober();
+        client
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum Compression {
     #[default]
     Gzip,
@@ -93,6 +143,16 @@ pub enum Compression {
     None,
 }
 
+impl Compression {
+    fn encoding(&self) -> Option<CompressionEncoding> {
+        match self {
+            Compression::Gzip => Some(CompressionEncoding::Gzip),
+            Compression::Zstd => Some(CompressionEncoding::Zstd),
+            Compression::None => None,
+        }
+    }
+}
+
 #[derive(Debug, Default, Builder)]
 struct Inner {
     channel_manager: ChannelManager,
@@ -100,6 +160,17 @@ struct Inner {
     peers: Arc<RwLock<Vec<String>>>,
     load_balance: Loadbalancer,
     compression: Compression,
+    #[builder(default)]
+    retry_policy: RetryPolicy,
+    #[builder(default)]
+    max_request_size: Option<usize>,
+    #[builder(default)]
+    health: Arc<HealthTracker>,
+    // Interval and timeout of the health check probes, if enabled.
+    #[builder(default)]
+    probe: Option<(Duration, Duration)>,
+    #[builder(setter(skip))]
+    prober_started: AtomicBool,
 }
 
 impl InnerBuilder {
@@ -112,13 +183,55 @@ impl InnerBuilder {
 impl Inner {
     fn set_peers(&self, peers: Vec<String>) {
         let mut guard = self.peers.write();
+        self.health.retain_peers(&peers);
         *guard = peers;
     }
 
-    fn get_peer(&self) -> Option<String> {
+    /// Pick a healthy peer, other than `exclude` if given.
+    fn get_peer(&self, exclude: Option<&str>) -> Option<String> {
         let guard = self.peers.read();
+        if exclude.is_some() || self.health.has_ejected() {
+            let candidates: Vec<String> = guard
+                .iter()
+                .filter(|peer| self.health.is_healthy(peer) && exclude != Some(peer.as_str()))
+                .cloned()
+                .collect();
+            // Fall back to all peers if every one of them is ejected or excluded.
+            if !candidates.is_empty() {
+                return self.load_balance.get_peer(&candidates).cloned();
+            }
+        }
         self.load_balance.get_peer(&guard).cloned()
     }
+
+    async fn probe_peer(&self, addr: &str, timeout: Duration) -> bool {
+        let Ok(channel) = self.channel_manager.get(addr) else {
+            return false;
+        };
+        let mut client = HealthCheckClient::new(channel);
+        matches!(
+            tokio::time::timeout(timeout, client.health_check(HealthCheckRequest {})).await,
+            Ok(Ok(_))
+        )
+    }
+}
+
+async fn probe_peers_in_loop(inner: Weak<Inner>, interval: Duration, timeout: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+        // Stop probing once the client is dropped.
+        let Some(inner) = inner.upgrade() else {
+            break;
+        };
+        let peers = inner.peers.read().clone();
+        let probes = peers.iter().map(|peer| async {
+            let healthy = inner.probe_peer(peer, timeout).await;
+            inner.health.record_probe(peer, healthy);
+        });
+        futures::future::join_all(probes).await;
+    }
 }
 
 impl Client {
@@ -175,10 +288,32 @@ impl Client {
         self.inner.set_peers(urls);
     }
 
+    /// Start probing the peers once, if enabled and within a tokio runtime.
+    fn start_prober(&self) {
+        let Some((interval, timeout)) = self.inner.probe else {
+            return;
+        };
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if self.inner.prober_started.load(Ordering::Relaxed)
+            || self.inner.prober_started.swap(true, Ordering::Relaxed)
+        {
+            return;
+        }
+        let inner = Arc::downgrade(&self.inner);
+        handle.spawn(probe_peers_in_loop(inner, interval, timeout));
+    }
+
     fn find_channel(&self) -> Result<(String, Channel)> {
+        self.find_channel_except(None)
+    }
+
+    fn find_channel_except(&self, exclude: Option<&str>) -> Result<(String, Channel)> {
+        self.start_prober();
         let addr = self
             .inner
-            .get_peer()
+            .get_peer(exclude)
             .context(error::IllegalGrpcClientStateSnafu {
                 err_msg: "No available peer found",
             })?;
@@ -189,28 +324,82 @@ impl Client {
     }
 
     pub(crate) fn make_database_client(&self) -> Result<DatabaseClient> {
-        let (_, channel) = self.find_channel()?;
+        self.make_database_client_except(None)
+    }
+
+    /// Make a database client connected to a peer other than `exclude`,
+    /// unless it is the only available one.
+    pub(crate) fn make_database_client_except(
+        &self,
+        exclude: Option<&str>,
+    ) -> Result<DatabaseClient> {
+        let (peer, channel) = self.find_channel_except(exclude)?;
         let mut client = GreptimeDatabaseClient::new(channel)
             .max_decoding_message_size(MAX_MESSAGE_SIZE)
             .accept_compressed(CompressionEncoding::Gzip)
             .accept_compressed(CompressionEncoding::Zstd);
-        match self.inner.compression {
-            Compression::Gzip => {
-                client = client.send_compressed(CompressionEncoding::Gzip);
-            }
-            Compression::Zstd => {
-                client = client.send_compressed(CompressionEncoding::Zstd);
-            }
-            Compression::None => {}
+        if let Some(encoding) = self.inner.compression.encoding() {
+            client = client.send_compressed(encoding);
         }
-        Ok(DatabaseClient { inner: client })
+        Ok(DatabaseClient {
+            peer,
+            inner: client,
+        })
+    }
+
+    #[cfg(feature = "flight")]
+    pub(crate) fn make_flight_client(&self) -> Result<FlightClient> {
+        let (peer, channel) = self.find_channel()?;
+        let mut client = FlightServiceClient::new(channel)
+            .max_decoding_message_size(MAX_MESSAGE_SIZE)
+            .accept_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Zstd);
+        if let Some(encoding) = self.inner.compression.encoding() {
+            client = client.send_compressed(encoding);
+        }
+        Ok(FlightClient {
+            peer,
+            inner: client,
+        })
+    }
+
+    /// Report the outcome of a request sent to `peer` for health tracking.
+    pub(crate) fn record_peer_result<T>(&self, peer: &str, result: &Result<T>) {
+        self.inner.health.record_request(peer, result);
+    }
+
+    /// Get the health state of every configured peer. All peers are reported
+    /// healthy unless [`ClientBuilder::peer_health_check`] is enabled.
+    pub fn peer_health(&self) -> Vec<PeerHealth> {
+        let peers = self.inner.peers.read();
+        peers
+            .iter()
+            .map(|peer| self.inner.health.peer_health(peer))
+            .collect()
+    }
+
+    pub(crate) fn retry_policy(&self) -> &RetryPolicy {
+        &self.inner.retry_policy
+    }
+
+    pub(crate) fn max_request_size(&self) -> usize {
+        self.inner.max_request_size.unwrap_or(MAX_MESSAGE_SIZE)
     }
 
     pub async fn health_check(&self) -> Result<()> {
-        let (_, channel) = self.find_channel()?;
-        let mut client = HealthCheckClient::new(channel);
-        client.health_check(HealthCheckRequest {}).await?;
-        Ok(())
+        self.retry_policy()
+            .retry(|| async {
+                let (peer, channel) = self.find_channel()?;
+                let mut client = HealthCheckClient::new(channel);
+                let result = client
+                    .health_check(HealthCheckRequest {})
+                    .await
+                    .map(|_| ())
+                    .map_err(Into::into);
+                self.record_peer_result(&peer, &result);
+                result
+            })
+            .await
     }
 }
 
@@ -229,8 +418,13 @@ where
 mod tests {
     use std::collections::HashSet;
 
-    use super::Inner;
-    use crate::load_balance::Loadbalancer;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use super::{ClientBuilder, Compression, Inner, InnerBuilder};
+    use crate::channel_manager::ChannelManager;
+    use crate::health::{HealthCheckConfig, HealthTracker};
+    use crate::load_balance::{Loadbalancer, RoundRobin};
 
     fn mock_peers() -> Vec<String> {
         vec![
@@ -248,14 +442,110 @@ mod tests {
             inner.load_balance,
             Loadbalancer::Random(crate::load_balance::Random)
         ));
-        assert!(inner.get_peer().is_none());
+        assert!(inner.get_peer(None).is_none());
 
         let peers = mock_peers();
         inner.set_peers(peers.clone());
         let all: HashSet<String> = peers.into_iter().collect();
 
         for _ in 0..20 {
-            assert!(all.contains(&inner.get_peer().unwrap()));
+            assert!(all.contains(&inner.get_peer(None).unwrap()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inner_round_robin() {
+        let inner = InnerBuilder::default()
+            .channel_manager(ChannelManager::default())
+            .load_balance(Loadbalancer::from(RoundRobin::default()))
+            .compression(Compression::None)
+            .peers(mock_peers())
+            .build()
+            .unwrap();
+
+        let picked: Vec<String> = (0..6).map(|_| inner.get_peer(None).unwrap()).collect();
+        assert_eq!(picked[..3], picked[3..]);
+        let all: HashSet<String> = picked.into_iter().collect();
+        assert_eq!(3, all.len());
+
+        inner.set_peers(vec!["127.0.0.1:3004".to_string()]);
+        for _ in 0..3 {
+            assert_eq!("127.0.0.1:3004", inner.get_peer(None).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inner_skip_ejected_peers() {
+        let config = HealthCheckConfig::new().failure_threshold(1);
+        let inner = InnerBuilder::default()
+            .channel_manager(ChannelManager::default())
+            .load_balance(Loadbalancer::default())
+            .compression(Compression::None)
+            .peers(mock_peers())
+            .health(Arc::new(HealthTracker::new(Some(config))))
+            .build()
+            .unwrap();
+
+        inner.health.record_probe("127.0.0.1:3001", false);
+        for _ in 0..20 {
+            assert_ne!("127.0.0.1:3001", inner.get_peer(None).unwrap());
         }
+
+        // every peer is ejected, fall back to all of them
+        inner.health.record_probe("127.0.0.1:3002", false);
+        inner.health.record_probe("127.0.0.1:3003", false);
+        assert!(inner.get_peer(None).is_some());
+
+        inner.health.record_probe("127.0.0.1:3002", true);
+        for _ in 0..20 {
+            assert_eq!("127.0.0.1:3002", inner.get_peer(None).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inner_get_peer_exclude() {
+        let inner = InnerBuilder::default()
+            .channel_manager(ChannelManager::default())
+            .load_balance(Loadbalancer::default())
+            .compression(Compression::None)
+            .peers(mock_peers())
+            .build()
+            .unwrap();
+
+        for _ in 0..20 {
+            assert_ne!(
+                "127.0.0.1:3001",
+                inner.get_peer(Some("127.0.0.1:3001")).unwrap()
+            );
+        }
+
+        // the only peer is picked even if excluded
+        inner.set_peers(vec!["127.0.0.1:3004".to_string()]);
+        assert_eq!(
+            "127.0.0.1:3004",
+            inner.get_peer(Some("127.0.0.1:3004")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_build_outside_runtime() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let builder = runtime.block_on(async {
+            ClientBuilder::default()
+                .peers(mock_peers())
+                .peer_health_check(HealthCheckConfig::default())
+        });
+
+        let client = builder.build();
+        assert!(!client.inner.prober_started.load(Ordering::Relaxed));
+
+        // Probing starts on the first request.
+        runtime.block_on(async {
+            client.make_database_client().unwrap();
+        });
+        assert!(client.inner.prober_started.load(Ordering::Relaxed));
     }
 }
@@ -13,17 +13,47 @@
 // limitations under the License.
 
 use crate::api::v1::auth_header::AuthScheme;
+use crate::api::v1::ddl_request::Expr as DdlExpr;
 use crate::api::v1::greptime_request::Request;
 use crate::api::v1::{
-    greptime_response, AffectedRows, AuthHeader, DeleteRequests, GreptimeRequest, InsertRequest,
-    InsertRequests, RequestHeader, RowInsertRequests,
+    greptime_response, AffectedRows, AlterExpr, AuthHeader, CreateDatabaseExpr, CreateTableExpr,
+    DdlRequest, DeleteRequests, DropTableExpr, GreptimeRequest, InsertRequest, InsertRequests,
+    RequestHeader, RowDeleteRequests, RowInsertRequests, TruncateTableExpr,
 };
+#[cfg(feature = "flight")]
+use crate::api::v1::{query_request, QueryRequest};
+use crate::bulk_writer::{BulkWriter, BulkWriterOptions, FlushResults};
+use crate::client::DatabaseClient;
+#[cfg(feature = "flight")]
+use crate::client::FlightClient;
+#[cfg(feature = "flight")]
+use crate::flight::{self, Output};
+use crate::reconnect::{ReconnectOptions, ReconnectingStreamInserter};
+use crate::split;
+use crate::spool::{SpoolOptions, SpooledWriter};
 use crate::stream_insert::StreamInserter;
 
-use crate::error::{IllegalDatabaseResponseSnafu, InvalidAsciiSnafu};
-use crate::{Client, Result};
-use snafu::OptionExt;
-use tonic::metadata::MetadataValue;
+use crate::error::{IllegalDatabaseResponseSnafu, PartialRowInsertSnafu};
+use crate::helpers::validate::Validate;
+use crate::hints::HINTS_KEY;
+use crate::metrics;
+use crate::trace;
+use crate::{Client, Hints, RequestOptions, Result};
+#[cfg(feature = "flight")]
+use arrow::record_batch::RecordBatch;
+#[cfg(feature = "flight")]
+use arrow_flight::decode::FlightDataDecoder;
+#[cfg(feature = "flight")]
+use arrow_flight::error::FlightError;
+#[cfg(feature = "flight")]
+use arrow_flight::Ticket;
+#[cfg(feature = "flight")]
+use futures::{Stream, TryStreamExt};
+use prost::{encoding, Message};
+use snafu::{OptionExt, ResultExt};
+use std::time::Instant;
+use tonic::metadata::MetadataMap;
+use tonic::Status;
 
 const DEFAULT_STREAMING_INSERTER_BUFFER_SIZE: usize = 1024;
 
@@ -36,6 +66,12 @@ pub struct Database {
 
     client: Client,
     auth_header: Option<AuthHeader>,
+
+    // Whether row inserts are validated before they are sent.
+    validate: bool,
+
+    // Hints of the requests without hints of their own.
+    hints: Hints,
 }
 
 impl Database {
@@ -51,6 +87,8 @@ impl Database {
             dbname: dbname.into(),
             client,
             auth_header: None,
+            validate: false,
+            hints: Hints::default(),
         }
     }
 
@@ -71,25 +109,119 @@ impl Database {
         });
     }
 
+    /// Validate row insert requests with [`Validate`] before sending them, so
+    /// that malformed rows fail with the row and column at fault. Default is
+    /// false.
+    pub fn set_validate(&mut self, validate: bool) {
+        self.validate = validate;
+    }
+
+    /// Get the default hints of this client
+    pub fn hints(&self) -> &Hints {
+        &self.hints
+    }
+
+    /// Set the hints sent with requests that have none of their own, such as
+    /// those given by [`RequestOptions::hints`]. Default is no hints.
+    pub fn set_hints(&mut self, hints: Hints) {
+        self.hints = hints;
+    }
+
     /// Write insert requests to GreptimeDB and get rows written
     #[deprecated(note = "Use row_insert instead.")]
     pub async fn insert(&self, requests: Vec<InsertRequest>) -> Result<u32> {
-        self.handle(Request::Inserts(InsertRequests { inserts: requests }), None)
-            .await
+        self.handle(
+            Request::Inserts(InsertRequests { inserts: requests }),
+            &RequestOptions::default(),
+        )
+        .await
     }
 
     /// Write Row based insert requests to GreptimeDB and get rows written
+    ///
+    /// Requests larger than [`ClientBuilder::max_request_size`](crate::ClientBuilder::max_request_size)
+    /// are sent in several requests one after another, stopping at the first
+    /// failed one. A failure after the first request is reported as
+    /// [`Error::PartialRowInsert`](crate::Error::PartialRowInsert) with the
+    /// rows already written.
     pub async fn row_insert(&self, requests: RowInsertRequests) -> Result<u32> {
-        self.handle(Request::RowInserts(requests), None).await
+        self.row_insert_with_options(requests, &RequestOptions::default())
+            .await
+    }
+
+    /// Write Row based insert requests with [`RequestOptions`] to GreptimeDB
+    /// and get rows written
+    pub async fn row_insert_with_options(
+        &self,
+        requests: RowInsertRequests,
+        options: &RequestOptions,
+    ) -> Result<u32> {
+        let chunks = self.split_row_inserts(requests, options)?;
+
+        // The timeout covers all the requests.
+        let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+        let count = chunks.len();
+        let mut affected_rows = 0;
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            let result = self
+                .handle_before(Request::RowInserts(chunk), options, deadline)
+                .await;
+            affected_rows += match result {
+                Ok(rows) => rows,
+                Err(e) if index == 0 => return Err(e),
+                Err(e) => {
+                    return Err(e).context(PartialRowInsertSnafu {
+                        chunk: index,
+                        chunks: count,
+                        affected_rows,
+                    })
+                }
+            };
+        }
+        Ok(affected_rows)
+    }
+
+    /// Validate `requests` if enabled, and split them into requests of at most
+    /// the maximum request size.
+    pub(crate) fn split_row_inserts(
+        &self,
+        requests: RowInsertRequests,
+        options: &RequestOptions,
+    ) -> Result<Vec<RowInsertRequests>> {
+        if self.validate {
+            requests.validate()?;
+        }
+        // Leave room for the header and the key and length of the requests.
+        let max_size = self.client.max_request_size();
+        let overhead = self
+            .to_rpc_request_with_options(Request::RowInserts(Default::default()), options)
+            .encoded_len()
+            + encoding::encoded_len_varint(max_size as u64);
+        split::split_row_inserts(requests, max_size.saturating_sub(overhead))
     }
 
     /// Write Row based insert requests with hint to GreptimeDB and get rows written
+    ///
+    /// The hint is sent as is, prefer [`row_insert_with_hints`](Self::row_insert_with_hints)
+    /// which validates the hints.
     pub async fn row_insert_with_hint(
         &self,
         requests: RowInsertRequests,
         hint: &str,
     ) -> Result<u32> {
-        self.handle(Request::RowInserts(requests), Some(hint)).await
+        let options = RequestOptions::new().metadata(HINTS_KEY, hint);
+        self.row_insert_with_options(requests, &options).await
+    }
+
+    /// Write Row based insert requests with [`Hints`] to GreptimeDB and get rows
+    /// written
+    pub async fn row_insert_with_hints(
+        &self,
+        requests: RowInsertRequests,
+        hints: &Hints,
+    ) -> Result<u32> {
+        let options = RequestOptions::new().hints(hints.clone());
+        self.row_insert_with_options(requests, &options).await
     }
 
     /// Initialise a streaming insert handle, using default buffer size `1024`
@@ -103,54 +235,269 @@ impl Database {
         channel_size: usize,
         hint: Option<&str>,
     ) -> Result<StreamInserter> {
-        let client = self.client.make_database_client()?.inner;
-        let hint = hint
-            .map(|value| {
-                MetadataValue::try_from(value).map_err(|_| InvalidAsciiSnafu { value }.build())
-            })
-            .transpose()?;
+        let mut options = RequestOptions::new();
+        if let Some(hint) = hint {
+            options = options.metadata(HINTS_KEY, hint);
+        }
+        self.streaming_inserter_with_options(channel_size, &options)
+    }
+
+    /// Initialise a streaming insert handle, using custom buffer size and
+    /// [`Hints`]
+    pub fn streaming_inserter_with_hints(
+        &self,
+        channel_size: usize,
+        hints: &Hints,
+    ) -> Result<StreamInserter> {
+        let options = RequestOptions::new().hints(hints.clone());
+        self.streaming_inserter_with_options(channel_size, &options)
+    }
 
+    /// Initialise a streaming insert handle, using custom buffer size and
+    /// [`RequestOptions`] applied to the whole stream
+    pub fn streaming_inserter_with_options(
+        &self,
+        channel_size: usize,
+        options: &RequestOptions,
+    ) -> Result<StreamInserter> {
+        let metadata = self.to_metadata(options)?;
+        let client = self.client.make_database_client()?;
         StreamInserter::new(
             client,
-            self.dbname().to_string(),
+            options.dbname.as_ref().unwrap_or(&self.dbname).clone(),
             self.auth_header.clone(),
             channel_size,
-            hint,
+            metadata,
+            options.timeout,
         )
     }
 
+    /// Initialise a streaming insert handle that re-establishes broken
+    /// streams and replays the requests not acknowledged yet, see
+    /// [`ReconnectingStreamInserter`]
+    pub fn reconnecting_streaming_inserter(
+        &self,
+        options: ReconnectOptions,
+    ) -> Result<ReconnectingStreamInserter> {
+        ReconnectingStreamInserter::new(self.clone(), options)
+    }
+
+    /// Initialise a bulk writer that batches rows and flushes them with
+    /// `row_insert` in the background. Flush results are delivered through the
+    /// returned [`FlushResults`].
+    pub fn bulk_writer(&self, options: BulkWriterOptions) -> (BulkWriter, FlushResults) {
+        BulkWriter::new(self.clone(), options)
+    }
+
+    /// Initialise a writer that spools requests to disk while the database
+    /// is unreachable and replays them later, see [`SpooledWriter`]
+    pub fn spooled_writer(&self, options: SpoolOptions) -> Result<SpooledWriter> {
+        SpooledWriter::new(self.clone(), options)
+    }
+
     /// Issue a delete to database
     pub async fn delete(&self, request: DeleteRequests) -> Result<u32> {
-        self.handle(Request::Deletes(request), None).await
+        self.delete_with_options(request, &RequestOptions::default())
+            .await
+    }
+
+    /// Issue a delete with [`RequestOptions`] to database
+    pub async fn delete_with_options(
+        &self,
+        request: DeleteRequests,
+        options: &RequestOptions,
+    ) -> Result<u32> {
+        self.handle(Request::Deletes(request), options).await
+    }
+
+    /// Issue a Row based delete to database and get rows deleted. Rows carry
+    /// the primary key and time index values of the rows to delete.
+    pub async fn row_delete(&self, requests: RowDeleteRequests) -> Result<u32> {
+        self.row_delete_with_options(requests, &RequestOptions::default())
+            .await
+    }
+
+    /// Issue a Row based delete with [`RequestOptions`] to database and get
+    /// rows deleted
+    pub async fn row_delete_with_options(
+        &self,
+        requests: RowDeleteRequests,
+        options: &RequestOptions,
+    ) -> Result<u32> {
+        self.handle(Request::RowDeletes(requests), options).await
+    }
+
+    /// Create a table, see [`CreateTableBuilder`](crate::helpers::ddl::CreateTableBuilder)
+    pub async fn create_table(&self, expr: CreateTableExpr) -> Result<u32> {
+        self.ddl(DdlExpr::CreateTable(expr)).await
+    }
+
+    /// Alter a table, see [`helpers::ddl`](crate::helpers::ddl) for building
+    /// the expression
+    pub async fn alter_table(&self, expr: AlterExpr) -> Result<u32> {
+        self.ddl(DdlExpr::Alter(expr)).await
+    }
+
+    /// Drop a table of this database
+    pub async fn drop_table(&self, table_name: &str, drop_if_exists: bool) -> Result<u32> {
+        self.ddl(DdlExpr::DropTable(DropTableExpr {
+            table_name: table_name.to_string(),
+            drop_if_exists,
+            ..Default::default()
+        }))
+        .await
+    }
+
+    /// Remove all rows of a table of this database
+    pub async fn truncate_table(&self, table_name: &str) -> Result<u32> {
+        self.ddl(DdlExpr::TruncateTable(TruncateTableExpr {
+            table_name: table_name.to_string(),
+            ..Default::default()
+        }))
+        .await
+    }
+
+    /// Create a new database
+    pub async fn create_database(&self, name: &str, create_if_not_exists: bool) -> Result<u32> {
+        self.ddl(DdlExpr::CreateDatabase(CreateDatabaseExpr {
+            schema_name: name.to_string(),
+            create_if_not_exists,
+            ..Default::default()
+        }))
+        .await
+    }
+
+    /// Catalog and schema left empty in the expression are filled by the
+    /// server from the dbname of this client.
+    async fn ddl(&self, expr: DdlExpr) -> Result<u32> {
+        self.handle(
+            Request::Ddl(DdlRequest { expr: Some(expr) }),
+            &RequestOptions::default(),
+        )
+        .await
+    }
+
+    /// Execute a SQL statement through Arrow Flight, and get either the rows
+    /// affected or a stream of record batches
+    #[cfg(feature = "flight")]
+    pub async fn sql(&self, sql: impl Into<String>) -> Result<Output> {
+        let request = Request::Query(QueryRequest {
+            query: Some(query_request::Query::Sql(sql.into())),
+        });
+        self.do_get(request).await
+    }
+
+    /// Write record batches into a table through Arrow Flight `DoPut`, and get
+    /// the rows written by every batch, in the order they were sent.
+    ///
+    /// The batches are sent in the Arrow IPC format, which is cheaper to
+    /// encode than rows. All batches must have the same schema.
+    #[cfg(feature = "flight")]
+    pub async fn record_batch_insert<S>(&self, table_name: &str, batches: S) -> Result<Vec<u32>>
+    where
+        S: Stream<Item = RecordBatch> + Send + 'static,
+    {
+        let metadata = flight::put_metadata(&self.dbname, self.auth_header.as_ref())?;
+        let (stream, encode_error) = flight::encode_put_stream(table_name, batches);
+        let mut request = tonic::Request::new(stream);
+        *request.metadata_mut() = metadata;
+
+        let FlightClient {
+            peer,
+            inner: mut client,
+        } = self.client.make_flight_client()?;
+        let response = client.do_put(request).await.map_err(Into::into);
+        self.client.record_peer_result(&peer, &response);
+
+        let affected_rows = flight::decode_put_results(response?.into_inner()).await;
+        if let Some(e) = encode_error.lock().take() {
+            return Err(flight::flight_error(e));
+        }
+        affected_rows
     }
 
-    async fn handle(&self, request: Request, hint: Option<&str>) -> Result<u32> {
-        let mut client = self.client.make_database_client()?.inner;
+    #[cfg(feature = "flight")]
+    async fn do_get(&self, request: Request) -> Result<Output> {
         let request = self.to_rpc_request(request);
-        let mut request = tonic::Request::new(request);
-        if let Some(hint) = hint {
-            let hint = MetadataValue::try_from(hint).map_err(|_| {
-                InvalidAsciiSnafu {
-                    value: hint.to_string(),
+        let ticket = Ticket {
+            ticket: request.encode_to_vec().into(),
+        };
+
+        let FlightClient {
+            peer,
+            inner: mut client,
+        } = self.client.make_flight_client()?;
+        let response = client.do_get(ticket).await.map_err(Into::into);
+        self.client.record_peer_result(&peer, &response);
+
+        let stream = response?.into_inner().map_err(FlightError::from);
+        flight::decode_output(FlightDataDecoder::new(stream)).await
+    }
+
+    async fn handle(&self, request: Request, options: &RequestOptions) -> Result<u32> {
+        let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+        self.handle_before(request, options, deadline).await
+    }
+
+    /// Like [`handle`](Self::handle), with the deadline computed by the caller
+    /// from `options.timeout`.
+    async fn handle_before(
+        &self,
+        request: Request,
+        options: &RequestOptions,
+        deadline: Option<Instant>,
+    ) -> Result<u32> {
+        let mut metadata = self.to_metadata(options)?;
+        let request = self.to_rpc_request_with_options(request, options);
+        let span = trace::handle_span(&request);
+        span.inject(&mut metadata);
+        let start = Instant::now();
+
+        let retry = self.client.retry_policy().retry(|| async {
+            let DatabaseClient {
+                peer,
+                inner: mut client,
+            } = self.client.make_database_client()?;
+            span.record_peer(&peer);
+            let mut request = tonic::Request::new(request.clone());
+            *request.metadata_mut() = metadata.clone();
+            let response = match deadline {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    request.set_timeout(timeout);
+                    match tokio::time::timeout(timeout, client.handle(request)).await {
+                        Ok(response) => response,
+                        // The deadline of the caller expired, which is no
+                        // failure of the peer.
+                        Err(_) => return Err(Status::deadline_exceeded("request timed out").into()),
+                    }
                 }
-                .build()
-            })?;
-            request.metadata_mut().insert("x-greptime-hints", hint);
-        }
-        let response = client
-            .handle(request)
-            .await?
-            .into_inner()
-            .response
-            .context(IllegalDatabaseResponseSnafu {
-                err_msg: "GreptimeResponse is empty",
-            })?;
-        let greptime_response::Response::AffectedRows(AffectedRows { value }) = response;
-        Ok(value)
+                None => client.handle(request).await,
+            }
+            .map_err(Into::into);
+            self.client.record_peer_result(&peer, &response);
+            let response =
+                response?
+                    .into_inner()
+                    .response
+                    .context(IllegalDatabaseResponseSnafu {
+                        err_msg: "GreptimeResponse is empty",
+                    })?;
+            let greptime_response::Response::AffectedRows(AffectedRows { value }) = response;
+            Ok(value)
+        });
+        let result = span.instrument(retry).await;
+        span.record_result(&result);
+        metrics::record_request(&request, start, &result);
+        result
+    }
+
+    pub(crate) fn client(&self) -> &Client {
+        &self.client
     }
 
     #[inline]
-    fn to_rpc_request(&self, request: Request) -> GreptimeRequest {
+    pub(crate) fn to_rpc_request(&self, request: Request) -> GreptimeRequest {
         GreptimeRequest {
             header: Some(RequestHeader {
                 authorization: self.auth_header.clone(),
@@ -160,6 +507,30 @@ impl Database {
             request: Some(request),
         }
     }
+
+    /// The metadata of a request with `options`, with the default hints
+    /// unless the options have some.
+    fn to_metadata(&self, options: &RequestOptions) -> Result<MetadataMap> {
+        let mut metadata = options.to_metadata()?;
+        if !metadata.contains_key(HINTS_KEY) {
+            if let Some(hints) = self.hints.to_metadata_value()? {
+                metadata.insert(HINTS_KEY, hints);
+            }
+        }
+        Ok(metadata)
+    }
+
+    fn to_rpc_request_with_options(
+        &self,
+        request: Request,
+        options: &RequestOptions,
+    ) -> GreptimeRequest {
+        let mut request = self.to_rpc_request(request);
+        if let (Some(dbname), Some(header)) = (&options.dbname, &mut request.header) {
+            header.dbname = dbname.clone();
+        }
+        request
+    }
 }
 
 #[cfg(test)]
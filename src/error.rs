@@ -13,11 +13,15 @@
 // limitations under the License.
 
 use std::io;
+use std::time::Duration;
 
 use snafu::{Location, Snafu};
-use tonic::Status;
+use tonic::{Code, Status};
+
+use crate::StatusCode;
 
 #[derive(Debug, Snafu)]
+#[cfg_attr(feature = "metrics", derive(strum::IntoStaticStr))]
 #[snafu(visibility(pub))]
 pub enum Error {
     #[snafu(display("Invalid client tls config, {}", msg))]
@@ -46,7 +50,11 @@ pub enum Error {
 
     // Server error carried in Tonic Status's metadata.
     #[snafu(display("{}", msg))]
-    Server { status: Status, msg: String },
+    Server {
+        status: Status,
+        msg: String,
+        code: Option<StatusCode>,
+    },
 
     #[snafu(display("Illegal Database response: {err_msg}"))]
     IllegalDatabaseResponse { err_msg: String },
@@ -54,40 +62,370 @@ pub enum Error {
     #[snafu(display("Failed to send request with streaming: {}", err_msg))]
     ClientStreaming { err_msg: String, location: Location },
 
+    #[snafu(display("Background task failed, source: {}", source))]
+    JoinTask {
+        source: tokio::task::JoinError,
+        location: Location,
+    },
+
+    #[snafu(display("Bulk writer is closed"))]
+    BulkWriterClosed { location: Location },
+
+    #[snafu(display("Failed to convert FlightData: {}", err_msg))]
+    ConvertFlightData { err_msg: String, location: Location },
+
+    #[snafu(display("Illegal Flight messages, reason: {}", reason))]
+    IllegalFlightMessages { reason: String, location: Location },
+
+    #[snafu(display("Failed to decode FlightMetadata, source: {}", source))]
+    DecodeFlightMetadata {
+        source: prost::DecodeError,
+        location: Location,
+    },
+
+    #[snafu(display("Invalid table definition: {}", reason))]
+    InvalidTableDefinition { reason: String, location: Location },
+
+    #[snafu(display("Failed to serialize row: {}", reason))]
+    SerializeRow { reason: String, location: Location },
+
+    #[snafu(display("Cannot write {} into column {} of type {}", value, column, datatype))]
+    ColumnTypeMismatch {
+        column: String,
+        datatype: String,
+        value: String,
+        location: Location,
+    },
+
+    #[snafu(display("Failed to parse line protocol at line {}: {}", line, reason))]
+    ParseLineProtocol {
+        line: usize,
+        reason: String,
+        location: Location,
+    },
+
+    #[snafu(display("Invalid timestamp precision: {}", precision))]
+    InvalidPrecision {
+        precision: String,
+        location: Location,
+    },
+
+    #[snafu(display("Conflicting schema of column {} in table {}", column, table))]
+    ColumnSchemaConflict {
+        table: String,
+        column: String,
+        location: Location,
+    },
+
+    #[snafu(display("Failed to decompress snappy data: {}", err_msg))]
+    DecompressSnappy { err_msg: String, location: Location },
+
+    #[snafu(display("Failed to decode remote write request, source: {}", source))]
+    DecodeRemoteWrite {
+        source: prost::DecodeError,
+        location: Location,
+    },
+
+    #[snafu(display("Invalid remote write request: {}", reason))]
+    InvalidRemoteWrite { reason: String, location: Location },
+
+    #[snafu(display("Unsupported arrow type {} of column {}", datatype, column))]
+    UnsupportedArrowType {
+        column: String,
+        datatype: String,
+        location: Location,
+    },
+
+    #[snafu(display("Failed to cast arrow array of column {}: {}", column, err_msg))]
+    CastArrowArray {
+        column: String,
+        err_msg: String,
+        location: Location,
+    },
+
+    #[snafu(display("Spool I/O error, source: {}", source))]
+    SpoolIo {
+        source: io::Error,
+        location: Location,
+    },
+
+    #[snafu(display("Spool is full"))]
+    SpoolFull { location: Location },
+
+    #[snafu(display("Invalid hint {}: {}", hint, reason))]
+    InvalidHint {
+        hint: String,
+        reason: String,
+        location: Location,
+    },
+
+    #[snafu(display("Invalid gRPC metadata key: {}", key))]
+    InvalidMetadataKey { key: String, location: Location },
+
+    #[snafu(display("Invalid connection string parameter {}: {}", param, reason))]
+    InvalidConnectionString {
+        param: String,
+        reason: String,
+        location: Location,
+    },
+
+    #[snafu(display("Invalid schema of table {}: {}", table, reason))]
+    InvalidSchema {
+        table: String,
+        reason: String,
+        location: Location,
+    },
+
+    #[snafu(display("Invalid row {} of table {}: {}", row, table, reason))]
+    InvalidRow {
+        table: String,
+        row: usize,
+        column: Option<usize>,
+        reason: String,
+        location: Location,
+    },
+
+    #[snafu(display(
+        "A row of table {} is {} bytes, larger than the maximum request size {}",
+        table,
+        size,
+        max_size
+    ))]
+    RequestTooLarge {
+        table: String,
+        size: usize,
+        max_size: usize,
+        location: Location,
+    },
+
+    /// A request split by [`Database::row_insert`](crate::Database::row_insert)
+    /// failed after the previous ones wrote `affected_rows` rows.
+    #[snafu(display(
+        "Failed to write split row insert request {} of {} after {} rows were written, source: {}",
+        chunk + 1,
+        chunks,
+        affected_rows,
+        source
+    ))]
+    PartialRowInsert {
+        /// Index of the failed request
+        chunk: usize,
+        chunks: usize,
+        affected_rows: u32,
+        #[snafu(source(from(Error, Box::new)))]
+        source: Box<Error>,
+        location: Location,
+    },
+
     #[snafu(display("Failed to parse ascii string: {}", value))]
     InvalidAscii {
         value: String,
         #[snafu(implicit)]
         location: Location,
     },
+
+    #[snafu(display("Retry deadline of {:?} exceeded", deadline))]
+    RetryDeadlineExceeded {
+        deadline: Duration,
+        location: Location,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+pub const INNER_ERROR_CODE: &str = "INNER_ERROR_CODE";
 pub const INNER_ERROR_MSG: &str = "INNER_ERROR_MSG";
 
+// Names of the metadata in newer versions of GreptimeDB.
+const GREPTIME_ERROR_CODE: &str = "x-greptime-err-code";
+const GREPTIME_ERROR_MSG: &str = "x-greptime-err-msg";
+
 impl From<Status> for Error {
     fn from(e: Status) -> Self {
-        fn get_metadata_value(e: &Status, key: &str) -> Option<String> {
-            e.metadata()
-                .get(key)
-                .and_then(|v| String::from_utf8(v.as_bytes().to_vec()).ok())
+        fn get_metadata_value(e: &Status, keys: [&str; 2]) -> Option<String> {
+            keys.iter().find_map(|key| {
+                e.metadata()
+                    .get(*key)
+                    .and_then(|v| String::from_utf8(v.as_bytes().to_vec()).ok())
+            })
         }
 
-        let msg = get_metadata_value(&e, INNER_ERROR_MSG).unwrap_or(e.to_string());
+        let msg =
+            get_metadata_value(&e, [INNER_ERROR_MSG, GREPTIME_ERROR_MSG]).unwrap_or(e.to_string());
+        let code = get_metadata_value(&e, [INNER_ERROR_CODE, GREPTIME_ERROR_CODE])
+            .and_then(|code| code.parse().ok())
+            .and_then(StatusCode::from_u32);
 
-        Self::Server { status: e, msg }
+        Self::Server {
+            status: e,
+            msg,
+            code,
+        }
     }
 }
 
 impl Error {
     /// Indicate if the error is retriable
+    ///
+    /// A server error is retriable if its [`StatusCode`] is, or if it has no
+    /// status code and the gRPC status indicates a transient failure. Failing
+    /// to connect to a peer or to stream to it is retriable, other client
+    /// side errors are not.
     pub fn is_retriable(&self) -> bool {
-        !matches!(
-            self,
-            Self::InvalidTlsConfig { .. }
-                | Self::MissingField { .. }
-                | Self::InvalidConfigFilePath { .. }
-        )
+        match self {
+            Self::Server {
+                code: Some(code), ..
+            } => code.is_retriable(),
+            Self::Server { status, .. } => matches!(
+                status.code(),
+                Code::Unknown
+                    | Code::Internal
+                    | Code::Unavailable
+                    | Code::ResourceExhausted
+                    | Code::Aborted
+            ),
+            Self::CreateChannel { .. } | Self::ClientStreaming { .. } => true,
+            Self::PartialRowInsert { source, .. } => source.is_retriable(),
+            _ => false,
+        }
+    }
+
+    /// Indicate if the server could not be reached, so the same request may
+    /// succeed later as is.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Server { status, .. } => status.code() == Code::Unavailable,
+            Self::CreateChannel { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// The GreptimeDB status code of a server error.
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            Self::Server { code, .. } => *code,
+            Self::PartialRowInsert { source, .. } => source.status_code(),
+            _ => None,
+        }
+    }
+
+    /// The gRPC status of a server error.
+    pub fn status(&self) -> Option<&Status> {
+        match self {
+            Self::Server { status, .. } => Some(status),
+            Self::PartialRowInsert { source, .. } => source.status(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use snafu::ResultExt;
+    use tonic::metadata::{MetadataKey, MetadataMap};
+
+    use super::*;
+
+    fn server_error(code: Code, metadata: &[(&'static str, &'static str)]) -> Error {
+        let mut map = MetadataMap::new();
+        for (key, value) in metadata {
+            let key = MetadataKey::from_bytes(key.as_bytes()).unwrap();
+            map.insert(key, value.parse().unwrap());
+        }
+        Status::with_metadata(code, "failed", map).into()
+    }
+
+    #[test]
+    fn test_server_error() {
+        let err = server_error(
+            Code::Internal,
+            &[
+                (INNER_ERROR_CODE, "4001"),
+                (INNER_ERROR_MSG, "Table not found"),
+            ],
+        );
+        assert_eq!(Some(StatusCode::TableNotFound), err.status_code());
+        assert_eq!("Table not found", err.to_string());
+        assert_eq!(Code::Internal, err.status().unwrap().code());
+        assert!(!err.is_retriable());
+
+        let err = server_error(Code::Unavailable, &[(GREPTIME_ERROR_CODE, "6001")]);
+        assert_eq!(Some(StatusCode::RateLimited), err.status_code());
+        assert!(err.is_retriable());
+
+        // Without a status code, the gRPC status decides.
+        let err = server_error(Code::Unavailable, &[(INNER_ERROR_CODE, "abc")]);
+        assert_eq!(None, err.status_code());
+        assert!(err.is_retriable());
+        assert!(!server_error(Code::InvalidArgument, &[]).is_retriable());
+    }
+
+    #[test]
+    fn test_partial_row_insert() {
+        let err = Err::<(), _>(server_error(
+            Code::Unavailable,
+            &[(GREPTIME_ERROR_CODE, "6001")],
+        ))
+        .context(PartialRowInsertSnafu {
+            chunk: 1usize,
+            chunks: 3usize,
+            affected_rows: 10u32,
+        })
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Failed to write split row insert request 2 of 3 after 10 rows"));
+        assert_eq!(Some(StatusCode::RateLimited), err.status_code());
+        assert!(err.is_retriable());
+    }
+
+    #[test]
+    fn test_client_errors_not_retriable() {
+        let errors = [
+            InvalidConnectionStringSnafu {
+                param: "host",
+                reason: "empty",
+            }
+            .build(),
+            SerializeRowSnafu { reason: "enum" }.build(),
+            ColumnTypeMismatchSnafu {
+                column: "host",
+                datatype: "Int64",
+                value: "string",
+            }
+            .build(),
+            ParseLineProtocolSnafu {
+                line: 1usize,
+                reason: "no field",
+            }
+            .build(),
+            UnsupportedArrowTypeSnafu {
+                column: "v",
+                datatype: "Struct",
+            }
+            .build(),
+            InvalidTableDefinitionSnafu {
+                reason: "no column",
+            }
+            .build(),
+            SpoolFullSnafu.build(),
+            Err::<(), _>(io::Error::other("disk"))
+                .context(SpoolIoSnafu)
+                .unwrap_err(),
+            BulkWriterClosedSnafu.build(),
+            InvalidHintSnafu {
+                hint: "ttl",
+                reason: "invalid duration",
+            }
+            .build(),
+            InvalidMetadataKeySnafu { key: "x key" }.build(),
+        ];
+        for err in errors {
+            assert!(!err.is_retriable(), "{err}");
+        }
+
+        assert!(ClientStreamingSnafu { err_msg: "closed" }
+            .build()
+            .is_retriable());
     }
 }
@@ -0,0 +1,317 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::decode::{DecodedFlightData, DecodedPayload, FlightDataDecoder};
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::{FlightData, FlightDescriptor, PutResult};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use futures::stream::BoxStream;
+use futures::{future, Stream, StreamExt};
+use parking_lot::Mutex;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use snafu::{OptionExt, ResultExt};
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::Streaming;
+
+use crate::api::v1::auth_header::AuthScheme;
+use crate::api::v1::{AuthHeader, Basic, FlightMetadata, Token};
+use crate::error::{
+    ConvertFlightDataSnafu, DecodeFlightMetadataSnafu, IllegalFlightMessagesSnafu,
+    InvalidAsciiSnafu, Result,
+};
+use crate::Error;
+
+/// Header carrying the database of a `DoPut` request, which has no
+/// [`RequestHeader`](crate::api::v1::RequestHeader).
+const DBNAME_HEADER: &str = "x-greptime-db-name";
+
+/// Output of a query executed by [`Database::sql`](crate::Database::sql).
+pub enum Output {
+    /// Rows affected by a statement that returns no data, e.g. `INSERT`
+    AffectedRows(u32),
+    /// Record batches returned by a query, e.g. `SELECT`
+    RecordBatches(RecordBatchStream),
+}
+
+/// Stream of the [`RecordBatch`]es returned by a query, all sharing the same
+/// schema.
+pub struct RecordBatchStream {
+    schema: SchemaRef,
+    inner: BoxStream<'static, Result<RecordBatch>>,
+}
+
+impl RecordBatchStream {
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+impl Stream for RecordBatchStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+/// Decode the flight messages of a `DoGet` response. GreptimeDB answers with
+/// either a single message carrying [`FlightMetadata`], or a schema followed
+/// by record batches.
+pub(crate) async fn decode_output(mut decoder: FlightDataDecoder) -> Result<Output> {
+    let first = decoder
+        .next()
+        .await
+        .context(IllegalFlightMessagesSnafu {
+            reason: "Expect at least one flight message",
+        })?
+        .map_err(flight_error)?;
+
+    match first.payload {
+        DecodedPayload::None => {
+            let metadata = FlightMetadata::decode(first.inner.app_metadata)
+                .context(DecodeFlightMetadataSnafu)?;
+            let affected_rows = metadata.affected_rows.context(IllegalFlightMessagesSnafu {
+                reason: "Expect affected rows in flight metadata",
+            })?;
+            Ok(Output::AffectedRows(affected_rows.value))
+        }
+        DecodedPayload::Schema(schema) => {
+            let inner = decoder
+                .filter_map(|data| async move {
+                    match data {
+                        Ok(DecodedFlightData {
+                            payload: DecodedPayload::RecordBatch(batch),
+                            ..
+                        }) => Some(Ok(batch)),
+                        Ok(_) => None,
+                        Err(e) => Some(Err(flight_error(e))),
+                    }
+                })
+                .boxed();
+            Ok(Output::RecordBatches(RecordBatchStream { schema, inner }))
+        }
+        DecodedPayload::RecordBatch(_) => IllegalFlightMessagesSnafu {
+            reason: "First flight message cannot be RecordBatch",
+        }
+        .fail(),
+    }
+}
+
+/// Keep server errors as [`Error::Server`] so they can be told apart from
+/// malformed flight data.
+pub(crate) fn flight_error(e: FlightError) -> Error {
+    match e {
+        FlightError::Tonic(status) => status.into(),
+        e => ConvertFlightDataSnafu {
+            err_msg: e.to_string(),
+        }
+        .build(),
+    }
+}
+
+/// App metadata of every record batch sent by `DoPut`, answered by a
+/// [`DoPutResponse`] with the same `request_id`.
+#[derive(Serialize)]
+struct DoPutMetadata {
+    request_id: i64,
+}
+
+#[derive(Deserialize)]
+struct DoPutResponse {
+    request_id: i64,
+    affected_rows: u64,
+}
+
+/// Encode `batches` into the `DoPut` stream of `table_name`. The table is
+/// named by the descriptor of the first message, and every record batch is
+/// tagged with its 1-based index as the request id.
+///
+/// Batches are not split, so that the server answers once per batch. An
+/// encoding error ends the stream and is kept in the returned slot.
+pub(crate) fn encode_put_stream<S>(
+    table_name: &str,
+    batches: S,
+) -> (
+    impl Stream<Item = FlightData> + Send + 'static,
+    Arc<Mutex<Option<FlightError>>>,
+)
+where
+    S: Stream<Item = RecordBatch> + Send + 'static,
+{
+    let encoder = FlightDataEncoderBuilder::new()
+        .with_max_flight_data_size(usize::MAX)
+        .with_flight_descriptor(Some(FlightDescriptor::new_path(vec![
+            table_name.to_string()
+        ])))
+        .build(batches.map(Ok));
+
+    let error = Arc::new(Mutex::new(None));
+    let slot = error.clone();
+    let mut request_id = 0;
+    let stream = encoder.scan((), move |_, data| {
+        let data = match data {
+            // the schema message has no body
+            Ok(mut data) if !data.data_body.is_empty() => {
+                request_id += 1;
+                let metadata = DoPutMetadata { request_id };
+                match serde_json::to_vec(&metadata) {
+                    Ok(metadata) => {
+                        data.app_metadata = metadata.into();
+                        Some(data)
+                    }
+                    Err(e) => {
+                        *slot.lock() = Some(FlightError::ExternalError(Box::new(e)));
+                        None
+                    }
+                }
+            }
+            Ok(data) => Some(data),
+            Err(e) => {
+                *slot.lock() = Some(e);
+                None
+            }
+        };
+        future::ready(data)
+    });
+    (stream, error)
+}
+
+/// Collect the rows written by every batch of a `DoPut`, in the order the
+/// batches were sent.
+pub(crate) async fn decode_put_results(mut results: Streaming<PutResult>) -> Result<Vec<u32>> {
+    let mut responses = Vec::new();
+    while let Some(result) = results.next().await {
+        let result = result?;
+        let response: DoPutResponse =
+            serde_json::from_slice(&result.app_metadata).map_err(|e| {
+                IllegalFlightMessagesSnafu {
+                    reason: format!("Invalid DoPut response: {e}"),
+                }
+                .build()
+            })?;
+        responses.push(response);
+    }
+    affected_rows(responses)
+}
+
+/// The rows written by every batch, ordered by request id.
+fn affected_rows(mut responses: Vec<DoPutResponse>) -> Result<Vec<u32>> {
+    responses.sort_by_key(|response| response.request_id);
+    responses
+        .into_iter()
+        .map(|response| {
+            u32::try_from(response.affected_rows).map_err(|_| {
+                IllegalFlightMessagesSnafu {
+                    reason: format!(
+                        "Affected rows {} of request {} overflow u32",
+                        response.affected_rows, response.request_id
+                    ),
+                }
+                .build()
+            })
+        })
+        .collect()
+}
+
+/// Metadata of a `DoPut` request, carrying the database and the credentials
+/// as HTTP style headers.
+pub(crate) fn put_metadata(dbname: &str, auth_header: Option<&AuthHeader>) -> Result<MetadataMap> {
+    let mut metadata = MetadataMap::new();
+    metadata.insert(DBNAME_HEADER, ascii_value(dbname.to_string())?);
+
+    if let Some(auth_scheme) = auth_header.and_then(|header| header.auth_scheme.as_ref()) {
+        let authorization = match auth_scheme {
+            AuthScheme::Basic(Basic { username, password }) => format!(
+                "Basic {}",
+                BASE64_STANDARD.encode(format!("{username}:{password}"))
+            ),
+            AuthScheme::Token(Token { token }) => format!("Bearer {token}"),
+        };
+        metadata.insert("authorization", ascii_value(authorization)?);
+    }
+    Ok(metadata)
+}
+
+fn ascii_value(value: String) -> Result<MetadataValue<tonic::metadata::Ascii>> {
+    MetadataValue::try_from(&value).map_err(|_| InvalidAsciiSnafu { value }.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Int32Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_encode_put_stream() {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int32, false)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(vec![1, 2]))]).unwrap();
+
+        let (stream, error) =
+            encode_put_stream("cpu", futures::stream::iter(vec![batch.clone(), batch]));
+        let messages: Vec<_> = stream.collect().await;
+        assert!(error.lock().is_none());
+
+        assert_eq!(3, messages.len());
+        assert_eq!(
+            vec!["cpu".to_string()],
+            messages[0].flight_descriptor.as_ref().unwrap().path
+        );
+        assert!(messages[0].app_metadata.is_empty());
+        assert_eq!(&b"{\"request_id\":1}"[..], &messages[1].app_metadata[..]);
+        assert_eq!(&b"{\"request_id\":2}"[..], &messages[2].app_metadata[..]);
+    }
+
+    #[test]
+    fn test_put_metadata() {
+        let auth = AuthHeader {
+            auth_scheme: Some(AuthScheme::Basic(Basic {
+                username: "user".to_string(),
+                password: "pass".to_string(),
+            })),
+        };
+        let metadata = put_metadata("public", Some(&auth)).unwrap();
+        assert_eq!("public", metadata.get(DBNAME_HEADER).unwrap());
+        assert_eq!("Basic dXNlcjpwYXNz", metadata.get("authorization").unwrap());
+
+        let metadata = put_metadata("public", None).unwrap();
+        assert!(metadata.get("authorization").is_none());
+        assert!(put_metadata("db\n", None).is_err());
+    }
+
+    #[test]
+    fn test_affected_rows() {
+        let response = |request_id, affected_rows| DoPutResponse {
+            request_id,
+            affected_rows,
+        };
+        assert_eq!(
+            vec![1, 2],
+            affected_rows(vec![response(2, 2), response(1, 1)]).unwrap()
+        );
+
+        let err = affected_rows(vec![response(1, u32::MAX as u64 + 1)]).unwrap_err();
+        assert!(matches!(err, Error::IllegalFlightMessages { .. }));
+    }
+}
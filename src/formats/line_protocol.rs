@@ -0,0 +1,411 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parser of the [InfluxDB line protocol](https://docs.influxdata.com/influxdb/v1/write_protocols/line_protocol_reference/).
+//!
+//! Every measurement becomes a table, with its tags as string tag columns, its
+//! fields as field columns and its timestamp as the
+//! [`TIMESTAMP_COLUMN`](super::TIMESTAMP_COLUMN).
+//!
+//! ```ignore
+//! let requests = line_protocol::parse(
+//!     "weather,location=us-midwest temperature=82 1465839830100400200",
+//!     Precision::Nanosecond,
+//! )?;
+//! database.row_insert(requests).await?;
+//! ```
+
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::api::v1::{ColumnDataType, ColumnSchema, RowInsertRequests, Value};
+use crate::error::{Error, InvalidPrecisionSnafu, ParseLineProtocolSnafu, Result};
+use crate::formats::TIMESTAMP_COLUMN;
+use crate::helpers::registry::SchemaRegistry;
+use crate::helpers::schema::{field, tag, timestamp};
+use crate::helpers::values::*;
+
+/// Precision of the timestamps of the lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Precision {
+    #[default]
+    Nanosecond,
+    Microsecond,
+    Millisecond,
+    Second,
+    Minute,
+    Hour,
+}
+
+impl Precision {
+    /// Datatype of the timestamp column, minutes and hours are stored as
+    /// seconds.
+    fn datatype(&self) -> ColumnDataType {
+        match self {
+            Precision::Nanosecond => ColumnDataType::TimestampNanosecond,
+            Precision::Microsecond => ColumnDataType::TimestampMicrosecond,
+            Precision::Millisecond => ColumnDataType::TimestampMillisecond,
+            Precision::Second | Precision::Minute | Precision::Hour => {
+                ColumnDataType::TimestampSecond
+            }
+        }
+    }
+
+    /// Convert a timestamp of this precision to the unit of [`Self::datatype`].
+    fn to_value(self, ts: i64) -> Option<Value> {
+        let value = match self {
+            Precision::Nanosecond => timestamp_nanosecond_value(ts),
+            Precision::Microsecond => timestamp_microsecond_value(ts),
+            Precision::Millisecond => timestamp_millisecond_value(ts),
+            Precision::Second => timestamp_second_value(ts),
+            Precision::Minute => timestamp_second_value(ts.checked_mul(60)?),
+            Precision::Hour => timestamp_second_value(ts.checked_mul(3600)?),
+        };
+        Some(value)
+    }
+
+    fn now(&self) -> Value {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        match self {
+            Precision::Nanosecond => timestamp_nanosecond_value(now.as_nanos() as i64),
+            Precision::Microsecond => timestamp_microsecond_value(now.as_micros() as i64),
+            Precision::Millisecond => timestamp_millisecond_value(now.as_millis() as i64),
+            Precision::Second | Precision::Minute | Precision::Hour => {
+                timestamp_second_value(now.as_secs() as i64)
+            }
+        }
+    }
+}
+
+/// Parse the `precision` query parameter of the InfluxDB write API.
+impl FromStr for Precision {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "n" | "ns" => Ok(Precision::Nanosecond),
+            "u" | "us" => Ok(Precision::Microsecond),
+            "ms" => Ok(Precision::Millisecond),
+            "s" => Ok(Precision::Second),
+            "m" => Ok(Precision::Minute),
+            "h" => Ok(Precision::Hour),
+            _ => InvalidPrecisionSnafu { precision: s }.fail(),
+        }
+    }
+}
+
+/// Parse line protocol text into one insert request per measurement. Empty
+/// lines and comments are skipped, lines without a timestamp use the current
+/// time.
+pub fn parse(text: &str, precision: Precision) -> Result<RowInsertRequests> {
+    let mut tables = SchemaRegistry::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.trim_start().trim_end_matches('\r');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parser = LineParser {
+            line,
+            line_no: line_no + 1,
+            pos: 0,
+        };
+        let (measurement, cells) = parser.parse(precision)?;
+        tables.table(&measurement).add_row(cells)?;
+    }
+    Ok(tables.flush())
+}
+
+struct LineParser<'a> {
+    line: &'a str,
+    line_no: usize,
+    pos: usize,
+}
+
+impl LineParser<'_> {
+    fn parse(&mut self, precision: Precision) -> Result<(String, Vec<(ColumnSchema, Value)>)> {
+        let measurement = self.read_escaped(b", ", b", \\");
+        if measurement.is_empty() {
+            return self.fail("missing measurement");
+        }
+
+        let mut cells = Vec::new();
+        if self.peek() == Some(b',') {
+            loop {
+                self.pos += 1;
+                let (key, value) = self.read_pair()?;
+                if value.is_empty() {
+                    return self.fail(format!("missing value of tag {key}"));
+                }
+                cells.push((tag(&key, ColumnDataType::String), string_value(value)));
+                if self.peek() != Some(b',') {
+                    break;
+                }
+            }
+        }
+
+        self.skip_spaces();
+        loop {
+            let key = self.read_key()?;
+            let value = self.read_field_value(&key)?;
+            cells.push((field(&key, datatype_of(&value)), value));
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b' ') | None => break,
+                Some(_) => return self.fail(format!("invalid value of field {key}")),
+            }
+        }
+
+        self.skip_spaces();
+        let ts = self.read_escaped(b" ", b"");
+        self.skip_spaces();
+        if self.peek().is_some() {
+            return self.fail("unexpected content after timestamp");
+        }
+        let ts = if ts.is_empty() {
+            precision.now()
+        } else {
+            match ts.parse().ok().and_then(|ts| precision.to_value(ts)) {
+                Some(ts) => ts,
+                None => return self.fail(format!("invalid timestamp {ts}")),
+            }
+        };
+        cells.push((timestamp(TIMESTAMP_COLUMN, precision.datatype()), ts));
+
+        Ok((measurement, cells))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.line.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(b' ') {
+            self.pos += 1;
+        }
+    }
+
+    /// Read until one of the unescaped `delimiters` or the end of the line,
+    /// removing the backslash before `escapes`.
+    fn read_escaped(&mut self, delimiters: &[u8], escapes: &[u8]) -> String {
+        let bytes = self.line.as_bytes();
+        let mut out = String::new();
+        let mut start = self.pos;
+        while let Some(b) = bytes.get(self.pos) {
+            if *b == b'\\' && bytes.get(self.pos + 1).is_some_and(|e| escapes.contains(e)) {
+                out.push_str(&self.line[start..self.pos]);
+                // keep the escaped byte as the start of the next segment
+                start = self.pos + 1;
+                self.pos += 2;
+                continue;
+            }
+            if delimiters.contains(b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        out.push_str(&self.line[start..self.pos]);
+        out
+    }
+
+    fn read_key(&mut self) -> Result<String> {
+        let key = self.read_escaped(b",= ", b",= \\");
+        if key.is_empty() {
+            return self.fail("missing key");
+        }
+        if self.peek() != Some(b'=') {
+            return self.fail(format!("missing value of {key}"));
+        }
+        self.pos += 1;
+        Ok(key)
+    }
+
+    fn read_pair(&mut self) -> Result<(String, String)> {
+        let key = self.read_key()?;
+        let value = self.read_escaped(b",= ", b",= \\");
+        Ok((key, value))
+    }
+
+    fn read_field_value(&mut self, key: &str) -> Result<Value> {
+        if self.peek() == Some(b'"') {
+            self.pos += 1;
+            let value = self.read_escaped(b"\"", b"\"\\");
+            if self.peek() != Some(b'"') {
+                return self.fail(format!("unterminated string value of field {key}"));
+            }
+            self.pos += 1;
+            return Ok(string_value(value));
+        }
+
+        let raw = self.read_escaped(b", ", b"");
+        let value = match raw.as_str() {
+            "t" | "T" | "true" | "True" | "TRUE" => Some(bool_value(true)),
+            "f" | "F" | "false" | "False" | "FALSE" => Some(bool_value(false)),
+            _ => {
+                if let Some(v) = raw.strip_suffix('i') {
+                    v.parse().ok().map(i64_value)
+                } else if let Some(v) = raw.strip_suffix('u') {
+                    v.parse().ok().map(u64_value)
+                } else {
+                    raw.parse::<f64>()
+                        .ok()
+                        .filter(|v| v.is_finite())
+                        .map(f64_value)
+                }
+            }
+        };
+        match value {
+            Some(value) => Ok(value),
+            None => self.fail(format!("invalid value {raw} of field {key}")),
+        }
+    }
+
+    fn fail<T>(&self, reason: impl Into<String>) -> Result<T> {
+        ParseLineProtocolSnafu {
+            line: self.line_no,
+            reason: reason.into(),
+        }
+        .fail()
+    }
+}
+
+fn datatype_of(value: &Value) -> ColumnDataType {
+    use crate::api::v1::value::ValueData;
+
+    match value.value_data {
+        Some(ValueData::I64Value(_)) => ColumnDataType::Int64,
+        Some(ValueData::U64Value(_)) => ColumnDataType::Uint64,
+        Some(ValueData::BoolValue(_)) => ColumnDataType::Boolean,
+        Some(ValueData::StringValue(_)) => ColumnDataType::String,
+        _ => ColumnDataType::Float64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::v1::{Rows, SemanticType};
+
+    fn rows(requests: &RowInsertRequests, table: &str) -> Rows {
+        requests
+            .inserts
+            .iter()
+            .find(|insert| insert.table_name == table)
+            .and_then(|insert| insert.rows.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+# comment
+weather,location=us-midwest,season=summer temperature=82,humidity=71i,up=t,note="hot, \"dry\"" 1465839830100400200
+weather,location=us-east temperature=75.5,count=3u 1465839830100400300
+cpu\ usage,host\,name=a\=b value=1 1465839830100400200
+"#;
+        let requests = parse(text, Precision::Nanosecond).unwrap();
+        assert_eq!(2, requests.inserts.len());
+
+        let weather = rows(&requests, "weather");
+        let columns: Vec<_> = weather
+            .schema
+            .iter()
+            .map(|c| c.column_name.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "location",
+                "season",
+                "temperature",
+                "humidity",
+                "up",
+                "note",
+                TIMESTAMP_COLUMN,
+                "count"
+            ],
+            columns
+        );
+        assert_eq!(SemanticType::Tag as i32, weather.schema[0].semantic_type);
+        assert_eq!(SemanticType::Field as i32, weather.schema[2].semantic_type);
+        assert_eq!(
+            ColumnDataType::TimestampNanosecond as i32,
+            weather.schema[6].datatype
+        );
+        assert_eq!(
+            vec![
+                string_value("us-midwest".to_string()),
+                string_value("summer".to_string()),
+                f64_value(82.0),
+                i64_value(71),
+                bool_value(true),
+                string_value("hot, \"dry\"".to_string()),
+                timestamp_nanosecond_value(1465839830100400200),
+                none_value(),
+            ],
+            weather.rows[0].values
+        );
+        assert_eq!(none_value(), weather.rows[1].values[1]);
+        assert_eq!(u64_value(3), weather.rows[1].values[7]);
+
+        let cpu = rows(&requests, "cpu usage");
+        assert_eq!("host,name", cpu.schema[0].column_name);
+        assert_eq!(string_value("a=b".to_string()), cpu.rows[0].values[0]);
+    }
+
+    #[test]
+    fn test_parse_precision() {
+        assert_eq!(Precision::Microsecond, "u".parse().unwrap());
+        assert!("d".parse::<Precision>().is_err());
+
+        let requests = parse("cpu value=1 2", Precision::Hour).unwrap();
+        let cpu = rows(&requests, "cpu");
+        assert_eq!(
+            ColumnDataType::TimestampSecond as i32,
+            cpu.schema[1].datatype
+        );
+        assert_eq!(timestamp_second_value(7200), cpu.rows[0].values[1]);
+
+        let requests = parse("cpu value=1", Precision::Millisecond).unwrap();
+        let cpu = rows(&requests, "cpu");
+        assert!(cpu.rows[0].values[1].value_data.is_some());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for line in [
+            "cpu",
+            "cpu ",
+            "cpu,host value=1",
+            "cpu,host= value=1",
+            "cpu value=",
+            "cpu value=abc",
+            "cpu value=1x",
+            "cpu value=\"open",
+            "cpu value=1 abc",
+            "cpu value=1 1 1",
+            ",host=a value=1",
+        ] {
+            let err = parse(line, Precision::Nanosecond).unwrap_err();
+            assert!(
+                matches!(err, Error::ParseLineProtocol { line: 1, .. }),
+                "{line}: {err}"
+            );
+        }
+
+        // conflicting types of a field across lines
+        assert!(parse("cpu value=1\ncpu value=1i", Precision::Nanosecond).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of other ingestion formats into
+//! [`RowInsertRequests`](crate::api::v1::RowInsertRequests).
+
+pub mod line_protocol;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "arrow")]
+pub mod record_batch;
+
+/// Name of the timestamp column of the tables created from other formats.
+pub const TIMESTAMP_COLUMN: &str = "greptime_timestamp";
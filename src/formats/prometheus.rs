@@ -0,0 +1,189 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decoder of [Prometheus remote write](https://prometheus.io/docs/concepts/remote_write_spec/)
+//! requests.
+//!
+//! Every metric becomes a table, with its labels as string tag columns, the
+//! sample value as the [`VALUE_COLUMN`] and the sample timestamp as the
+//! millisecond [`TIMESTAMP_COLUMN`].
+//!
+//! ```ignore
+//! // body of a `POST /api/v1/write` request
+//! let requests = prometheus::decode_remote_write(&body)?;
+//! database.row_insert(requests).await?;
+//! ```
+
+use greptime_proto::prometheus::remote::WriteRequest;
+use prost::Message;
+use snafu::ResultExt;
+
+use crate::api::v1::{ColumnDataType, RowInsertRequests};
+use crate::error::{
+    DecodeRemoteWriteSnafu, DecompressSnappySnafu, InvalidRemoteWriteSnafu, Result,
+};
+use crate::formats::TIMESTAMP_COLUMN;
+use crate::helpers::registry::SchemaRegistry;
+use crate::helpers::schema::{field, tag, timestamp};
+use crate::helpers::values::{f64_value, string_value, timestamp_millisecond_value};
+
+/// Name of the field column holding the sample values.
+pub const VALUE_COLUMN: &str = "greptime_value";
+
+/// Label holding the metric name, which is the table name instead of a tag.
+pub const METRIC_NAME_LABEL: &str = "__name__";
+
+/// Decode the snappy compressed protobuf body of a remote write request.
+pub fn decode_remote_write(body: &[u8]) -> Result<RowInsertRequests> {
+    let buf = snap::raw::Decoder::new()
+        .decompress_vec(body)
+        .map_err(|e| {
+            DecompressSnappySnafu {
+                err_msg: e.to_string(),
+            }
+            .build()
+        })?;
+    let request = WriteRequest::decode(buf.as_slice()).context(DecodeRemoteWriteSnafu)?;
+    to_insert_requests(request)
+}
+
+/// Convert a decoded remote write request, one row per sample.
+pub fn to_insert_requests(request: WriteRequest) -> Result<RowInsertRequests> {
+    let mut tables = SchemaRegistry::new();
+    for series in request.timeseries {
+        let mut metric = None;
+        let mut tags = Vec::with_capacity(series.labels.len());
+        for label in series.labels {
+            if label.name == METRIC_NAME_LABEL {
+                metric = Some(label.value);
+            } else {
+                tags.push((tag(&label.name, ColumnDataType::String), label.value));
+            }
+        }
+        let Some(metric) = metric else {
+            return InvalidRemoteWriteSnafu {
+                reason: format!("missing {METRIC_NAME_LABEL} label"),
+            }
+            .fail();
+        };
+
+        let table = tables.table(&metric);
+        for sample in series.samples {
+            let mut cells = Vec::with_capacity(tags.len() + 2);
+            cells.extend(
+                tags.iter()
+                    .map(|(column, value)| (column.clone(), string_value(value.clone()))),
+            );
+            cells.push((
+                field(VALUE_COLUMN, ColumnDataType::Float64),
+                f64_value(sample.value),
+            ));
+            cells.push((
+                timestamp(TIMESTAMP_COLUMN, ColumnDataType::TimestampMillisecond),
+                timestamp_millisecond_value(sample.timestamp),
+            ));
+            table.add_row(cells)?;
+        }
+    }
+    Ok(tables.flush())
+}
+
+#[cfg(test)]
+mod tests {
+    use greptime_proto::prometheus::remote::{Label, Sample, TimeSeries};
+
+    use super::*;
+    use crate::helpers::values::none_value;
+    use crate::Error;
+
+    fn label(name: &str, value: &str) -> Label {
+        Label {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn sample(value: f64, timestamp: i64) -> Sample {
+        Sample { value, timestamp }
+    }
+
+    #[test]
+    fn test_decode_remote_write() {
+        let request = WriteRequest {
+            timeseries: vec![
+                TimeSeries {
+                    labels: vec![label(METRIC_NAME_LABEL, "up"), label("job", "node")],
+                    samples: vec![sample(1.0, 1000), sample(0.0, 2000)],
+                },
+                TimeSeries {
+                    labels: vec![label(METRIC_NAME_LABEL, "up"), label("instance", "h1")],
+                    samples: vec![sample(1.0, 1000)],
+                },
+                TimeSeries {
+                    labels: vec![label(METRIC_NAME_LABEL, "load")],
+                    samples: vec![sample(0.5, 1000)],
+                },
+            ],
+            ..Default::default()
+        };
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .unwrap();
+
+        let requests = decode_remote_write(&body).unwrap();
+        assert_eq!(2, requests.inserts.len());
+
+        let up = &requests.inserts[0];
+        assert_eq!("up", up.table_name);
+        let rows = up.rows.as_ref().unwrap();
+        let columns: Vec<_> = rows.schema.iter().map(|c| c.column_name.as_str()).collect();
+        assert_eq!(
+            vec!["job", VALUE_COLUMN, TIMESTAMP_COLUMN, "instance"],
+            columns
+        );
+        assert_eq!(3, rows.rows.len());
+        assert_eq!(
+            vec![
+                string_value("node".to_string()),
+                f64_value(0.0),
+                timestamp_millisecond_value(2000),
+                none_value(),
+            ],
+            rows.rows[1].values
+        );
+        assert_eq!(none_value(), rows.rows[2].values[0]);
+
+        assert_eq!("load", requests.inserts[1].table_name);
+    }
+
+    #[test]
+    fn test_decode_remote_write_invalid() {
+        assert!(matches!(
+            decode_remote_write(b"not snappy").unwrap_err(),
+            Error::DecompressSnappy { .. }
+        ));
+
+        let request = WriteRequest {
+            timeseries: vec![TimeSeries {
+                labels: vec![label("job", "node")],
+                samples: vec![sample(1.0, 1000)],
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            to_insert_requests(request).unwrap_err(),
+            Error::InvalidRemoteWrite { .. }
+        ));
+    }
+}
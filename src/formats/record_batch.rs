@@ -0,0 +1,395 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of Arrow [`RecordBatch`]es into [`RowInsertRequest`]s.
+//!
+//! ```ignore
+//! let request = record_batch::to_insert_request(
+//!     "cpu",
+//!     &batch,
+//!     &[SemanticType::Tag, SemanticType::Timestamp, SemanticType::Field],
+//! )?;
+//! database.row_insert(RowInsertRequests { inserts: vec![request] }).await?;
+//! ```
+
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::datatypes::*;
+use arrow::record_batch::RecordBatch;
+use snafu::ensure;
+
+use crate::api::v1::column_data_type_extension::TypeExt;
+use crate::api::v1::{
+    ColumnDataType, ColumnDataTypeExtension, ColumnSchema, DecimalTypeExtension, Row,
+    RowInsertRequest, Rows, SemanticType, Value,
+};
+use crate::error::{
+    CastArrowArraySnafu, InvalidTableDefinitionSnafu, Result, UnsupportedArrowTypeSnafu,
+};
+use crate::helpers::values::*;
+
+/// Convert `batch` into a request inserting into `table_name`, with the
+/// semantic type of every column of `batch` in `semantic_types`.
+///
+/// Dictionary arrays are flattened into their value type, and the time zones
+/// of timestamps are dropped since the values are already UTC.
+pub fn to_insert_request(
+    table_name: &str,
+    batch: &RecordBatch,
+    semantic_types: &[SemanticType],
+) -> Result<RowInsertRequest> {
+    ensure!(
+        semantic_types.len() == batch.num_columns(),
+        InvalidTableDefinitionSnafu {
+            reason: format!(
+                "expect {} semantic types, got {}",
+                batch.num_columns(),
+                semantic_types.len()
+            ),
+        }
+    );
+
+    let arrow_schema = batch.schema();
+    let mut schema = Vec::with_capacity(batch.num_columns());
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    for ((field, array), semantic_type) in arrow_schema
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .zip(semantic_types)
+    {
+        let (datatype, datatype_extension) = column_datatype(field.name(), field.data_type())?;
+        schema.push(ColumnSchema {
+            column_name: field.name().clone(),
+            datatype: datatype as i32,
+            semantic_type: *semantic_type as i32,
+            datatype_extension,
+        });
+        columns.push(column_values(field.name(), array)?);
+    }
+
+    let rows = (0..batch.num_rows())
+        .map(|i| Row {
+            values: columns
+                .iter_mut()
+                .map(|column| std::mem::take(&mut column[i]))
+                .collect(),
+        })
+        .collect();
+
+    Ok(RowInsertRequest {
+        table_name: table_name.to_string(),
+        rows: Some(Rows { schema, rows }),
+    })
+}
+
+fn column_datatype(
+    column: &str,
+    datatype: &DataType,
+) -> Result<(ColumnDataType, Option<ColumnDataTypeExtension>)> {
+    let datatype = match datatype {
+        DataType::Boolean => ColumnDataType::Boolean,
+        DataType::Int8 => ColumnDataType::Int8,
+        DataType::Int16 => ColumnDataType::Int16,
+        DataType::Int32 => ColumnDataType::Int32,
+        DataType::Int64 => ColumnDataType::Int64,
+        DataType::UInt8 => ColumnDataType::Uint8,
+        DataType::UInt16 => ColumnDataType::Uint16,
+        DataType::UInt32 => ColumnDataType::Uint32,
+        DataType::UInt64 => ColumnDataType::Uint64,
+        DataType::Float32 => ColumnDataType::Float32,
+        DataType::Float64 => ColumnDataType::Float64,
+        DataType::Utf8 | DataType::LargeUtf8 => ColumnDataType::String,
+        DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => {
+            ColumnDataType::Binary
+        }
+        DataType::Date32 => ColumnDataType::Date,
+        DataType::Date64 => ColumnDataType::Datetime,
+        DataType::Timestamp(TimeUnit::Second, _) => ColumnDataType::TimestampSecond,
+        DataType::Timestamp(TimeUnit::Millisecond, _) => ColumnDataType::TimestampMillisecond,
+        DataType::Timestamp(TimeUnit::Microsecond, _) => ColumnDataType::TimestampMicrosecond,
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => ColumnDataType::TimestampNanosecond,
+        DataType::Time32(TimeUnit::Second) => ColumnDataType::TimeSecond,
+        DataType::Time32(TimeUnit::Millisecond) => ColumnDataType::TimeMillisecond,
+        DataType::Time64(TimeUnit::Microsecond) => ColumnDataType::TimeMicrosecond,
+        DataType::Time64(TimeUnit::Nanosecond) => ColumnDataType::TimeNanosecond,
+        DataType::Interval(IntervalUnit::YearMonth) => ColumnDataType::IntervalYearMonth,
+        DataType::Interval(IntervalUnit::DayTime) => ColumnDataType::IntervalDayTime,
+        DataType::Interval(IntervalUnit::MonthDayNano) => ColumnDataType::IntervalMonthDayNano,
+        DataType::Duration(TimeUnit::Second) => ColumnDataType::DurationSecond,
+        DataType::Duration(TimeUnit::Millisecond) => ColumnDataType::DurationMillisecond,
+        DataType::Duration(TimeUnit::Microsecond) => ColumnDataType::DurationMicrosecond,
+        DataType::Duration(TimeUnit::Nanosecond) => ColumnDataType::DurationNanosecond,
+        DataType::Decimal128(precision, scale) => {
+            let extension = ColumnDataTypeExtension {
+                type_ext: Some(TypeExt::DecimalType(DecimalTypeExtension {
+                    precision: *precision as i32,
+                    scale: *scale as i32,
+                })),
+            };
+            return Ok((ColumnDataType::Decimal128, Some(extension)));
+        }
+        DataType::Dictionary(_, value_type) => return column_datatype(column, value_type),
+        datatype => {
+            return UnsupportedArrowTypeSnafu {
+                column,
+                datatype: datatype.to_string(),
+            }
+            .fail();
+        }
+    };
+    Ok((datatype, None))
+}
+
+macro_rules! primitive_values {
+    ($array:expr, $arrow_type:ty, $value_fn:expr) => {
+        $array
+            .as_primitive::<$arrow_type>()
+            .iter()
+            .map(|v| v.map_or_else(none_value, $value_fn))
+            .collect()
+    };
+}
+
+fn column_values(column: &str, array: &ArrayRef) -> Result<Vec<Value>> {
+    let values = match array.data_type() {
+        DataType::Boolean => array
+            .as_boolean()
+            .iter()
+            .map(|v| v.map_or_else(none_value, bool_value))
+            .collect(),
+        DataType::Int8 => primitive_values!(array, Int8Type, i8_value),
+        DataType::Int16 => primitive_values!(array, Int16Type, i16_value),
+        DataType::Int32 => primitive_values!(array, Int32Type, i32_value),
+        DataType::Int64 => primitive_values!(array, Int64Type, i64_value),
+        DataType::UInt8 => primitive_values!(array, UInt8Type, u8_value),
+        DataType::UInt16 => primitive_values!(array, UInt16Type, u16_value),
+        DataType::UInt32 => primitive_values!(array, UInt32Type, u32_value),
+        DataType::UInt64 => primitive_values!(array, UInt64Type, u64_value),
+        DataType::Float32 => primitive_values!(array, Float32Type, f32_value),
+        DataType::Float64 => primitive_values!(array, Float64Type, f64_value),
+        DataType::Utf8 => string_values(array.as_string::<i32>().iter()),
+        DataType::LargeUtf8 => string_values(array.as_string::<i64>().iter()),
+        DataType::Binary => binary_values(array.as_binary::<i32>().iter()),
+        DataType::LargeBinary => binary_values(array.as_binary::<i64>().iter()),
+        DataType::FixedSizeBinary(_) => binary_values(array.as_fixed_size_binary().iter()),
+        DataType::Date32 => primitive_values!(array, Date32Type, date_value),
+        DataType::Date64 => primitive_values!(array, Date64Type, datetime_value),
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            primitive_values!(array, TimestampSecondType, timestamp_second_value)
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            primitive_values!(array, TimestampMillisecondType, timestamp_millisecond_value)
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            primitive_values!(array, TimestampMicrosecondType, timestamp_microsecond_value)
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            primitive_values!(array, TimestampNanosecondType, timestamp_nanosecond_value)
+        }
+        DataType::Time32(TimeUnit::Second) => {
+            primitive_values!(array, Time32SecondType, |v| time_second_value(v as i64))
+        }
+        DataType::Time32(TimeUnit::Millisecond) => {
+            primitive_values!(array, Time32MillisecondType, |v| {
+                time_millisecond_value(v as i64)
+            })
+        }
+        DataType::Time64(TimeUnit::Microsecond) => {
+            primitive_values!(array, Time64MicrosecondType, time_microsecond_value)
+        }
+        DataType::Time64(TimeUnit::Nanosecond) => {
+            primitive_values!(array, Time64NanosecondType, time_nanosecond_value)
+        }
+        DataType::Interval(IntervalUnit::YearMonth) => {
+            primitive_values!(array, IntervalYearMonthType, interval_year_month_value)
+        }
+        DataType::Interval(IntervalUnit::DayTime) => {
+            primitive_values!(array, IntervalDayTimeType, |v| {
+                // days in the high 32 bits and milliseconds in the low 32 bits
+                let (days, milliseconds) = IntervalDayTimeType::to_parts(v);
+                interval_day_time_value(((days as i64) << 32) | (milliseconds as u32 as i64))
+            })
+        }
+        DataType::Interval(IntervalUnit::MonthDayNano) => {
+            primitive_values!(array, IntervalMonthDayNanoType, |v| {
+                let (months, days, nanoseconds) = IntervalMonthDayNanoType::to_parts(v);
+                interval_month_day_nano_value(months, days, nanoseconds)
+            })
+        }
+        DataType::Duration(TimeUnit::Second) => {
+            primitive_values!(array, DurationSecondType, duration_second_value)
+        }
+        DataType::Duration(TimeUnit::Millisecond) => {
+            primitive_values!(array, DurationMillisecondType, duration_millisecond_value)
+        }
+        DataType::Duration(TimeUnit::Microsecond) => {
+            primitive_values!(array, DurationMicrosecondType, duration_microsecond_value)
+        }
+        DataType::Duration(TimeUnit::Nanosecond) => {
+            primitive_values!(array, DurationNanosecondType, duration_nanosecond_value)
+        }
+        DataType::Decimal128(_, _) => {
+            primitive_values!(array, Decimal128Type, decimal128_value)
+        }
+        DataType::Dictionary(_, value_type) => {
+            let array = arrow::compute::cast(array, value_type).map_err(|e| {
+                CastArrowArraySnafu {
+                    column,
+                    err_msg: e.to_string(),
+                }
+                .build()
+            })?;
+            return column_values(column, &array);
+        }
+        datatype => {
+            return UnsupportedArrowTypeSnafu {
+                column,
+                datatype: datatype.to_string(),
+            }
+            .fail();
+        }
+    };
+    Ok(values)
+}
+
+fn string_values<'a>(iter: impl Iterator<Item = Option<&'a str>>) -> Vec<Value> {
+    iter.map(|v| v.map_or_else(none_value, |v| string_value(v.to_string())))
+        .collect()
+}
+
+fn binary_values<'a>(iter: impl Iterator<Item = Option<&'a [u8]>>) -> Vec<Value> {
+    iter.map(|v| v.map_or_else(none_value, |v| binary_value(v.to_vec())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::*;
+
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn test_to_insert_request() {
+        let schema = Schema::new(vec![
+            Field::new(
+                "host",
+                DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+                true,
+            ),
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("+08:00".into())),
+                false,
+            ),
+            Field::new("cpu", DataType::Float64, true),
+            Field::new("price", DataType::Decimal128(10, 2), true),
+            Field::new("raw", DataType::LargeBinary, true),
+            Field::new("up", DataType::Interval(IntervalUnit::DayTime), true),
+        ]);
+        let host: DictionaryArray<Int32Type> = vec![Some("h1"), None].into_iter().collect();
+        let day_time = IntervalDayTimeType::make_value(1, 2);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(host),
+                Arc::new(TimestampMicrosecondArray::from(vec![1000, 2000]).with_timezone("+08:00")),
+                Arc::new(Float64Array::from(vec![Some(0.5), None])),
+                Arc::new(
+                    Decimal128Array::from(vec![Some(12345), None])
+                        .with_precision_and_scale(10, 2)
+                        .unwrap(),
+                ),
+                Arc::new(LargeBinaryArray::from_opt_vec(vec![Some(b"ab"), None])),
+                Arc::new(IntervalDayTimeArray::from(vec![Some(day_time), None])),
+            ],
+        )
+        .unwrap();
+
+        let request = to_insert_request(
+            "cpu",
+            &batch,
+            &[
+                SemanticType::Tag,
+                SemanticType::Timestamp,
+                SemanticType::Field,
+                SemanticType::Field,
+                SemanticType::Field,
+                SemanticType::Field,
+            ],
+        )
+        .unwrap();
+
+        assert_eq!("cpu", request.table_name);
+        let rows = request.rows.unwrap();
+        let datatypes: Vec<_> = rows.schema.iter().map(|c| c.datatype).collect();
+        assert_eq!(
+            vec![
+                ColumnDataType::String as i32,
+                ColumnDataType::TimestampMicrosecond as i32,
+                ColumnDataType::Float64 as i32,
+                ColumnDataType::Decimal128 as i32,
+                ColumnDataType::Binary as i32,
+                ColumnDataType::IntervalDayTime as i32,
+            ],
+            datatypes
+        );
+        assert_eq!(SemanticType::Tag as i32, rows.schema[0].semantic_type);
+        assert_eq!(
+            Some(TypeExt::DecimalType(DecimalTypeExtension {
+                precision: 10,
+                scale: 2,
+            })),
+            rows.schema[3].datatype_extension.clone().unwrap().type_ext
+        );
+
+        assert_eq!(
+            vec![
+                string_value("h1".to_string()),
+                timestamp_microsecond_value(1000),
+                f64_value(0.5),
+                decimal128_value(12345),
+                binary_value(b"ab".to_vec()),
+                interval_day_time_value((1 << 32) | 2),
+            ],
+            rows.rows[0].values
+        );
+        assert_eq!(
+            vec![
+                none_value(),
+                timestamp_microsecond_value(2000),
+                none_value(),
+                none_value(),
+                none_value(),
+                none_value(),
+            ],
+            rows.rows[1].values
+        );
+    }
+
+    #[test]
+    fn test_to_insert_request_invalid() {
+        let schema = Schema::new(vec![Field::new("n", DataType::Null, true)]);
+        let batch =
+            RecordBatch::try_new(Arc::new(schema), vec![Arc::new(NullArray::new(1))]).unwrap();
+
+        let err = to_insert_request("t", &batch, &[SemanticType::Field]).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedArrowType { column, .. } if column == "n"));
+
+        let err = to_insert_request("t", &batch, &[]).unwrap_err();
+        assert!(matches!(err, Error::InvalidTableDefinition { .. }));
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use dashmap::DashMap;
+use tonic::Code;
+
+use crate::Error;
+
+/// Config of the peer health tracking, enabled by
+/// [`ClientBuilder::peer_health_check`](crate::ClientBuilder::peer_health_check).
+///
+/// A peer is ejected from selection after `failure_threshold` consecutive
+/// failed requests or probes, and re-admitted after its next successful probe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthCheckConfig {
+    pub failure_threshold: u32,
+    pub probe_interval: Duration,
+    pub probe_timeout: Duration,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            probe_interval: Duration::from_secs(10),
+            probe_timeout: Duration::from_secs(3),
+        }
+    }
+}
+
+impl HealthCheckConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Consecutive failures after which a peer is ejected.
+    ///
+    /// Default is 3
+    pub fn failure_threshold(self, failure_threshold: u32) -> Self {
+        Self {
+            failure_threshold,
+            ..self
+        }
+    }
+
+    /// Interval between two health check probes of every peer.
+    ///
+    /// Default is 10s
+    pub fn probe_interval(self, probe_interval: Duration) -> Self {
+        Self {
+            probe_interval,
+            ..self
+        }
+    }
+
+    /// Timeout of a single health check probe.
+    ///
+    /// Default is 3s
+    pub fn probe_timeout(self, probe_timeout: Duration) -> Self {
+        Self {
+            probe_timeout,
+            ..self
+        }
+    }
+}
+
+/// Health state of a peer, see [`Client::peer_health`](crate::Client::peer_health).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerHealth {
+    pub addr: String,
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug, Default)]
+struct PeerStats {
+    consecutive_failures: u32,
+    ejected: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct HealthTracker {
+    config: Option<HealthCheckConfig>,
+    peers: DashMap<String, PeerStats>,
+}
+
+impl HealthTracker {
+    pub(crate) fn new(config: Option<HealthCheckConfig>) -> Self {
+        Self {
+            config,
+            peers: DashMap::new(),
+        }
+    }
+
+    pub(crate) fn has_ejected(&self) -> bool {
+        self.peers.iter().any(|stats| stats.ejected)
+    }
+
+    pub(crate) fn is_healthy(&self, addr: &str) -> bool {
+        !self.peers.get(addr).is_some_and(|stats| stats.ejected)
+    }
+
+    /// Record the outcome of a request sent to `addr`. Only errors caused by
+    /// the peer being unreachable count as failures.
+    pub(crate) fn record_request<T>(&self, addr: &str, result: &Result<T, Error>) {
+        match result {
+            Ok(_) => self.record_success(addr, false),
+            Err(e) if is_peer_failure(e) => self.record_failure(addr),
+            Err(_) => {}
+        }
+    }
+
+    /// Record the outcome of a health check probe of `addr`.
+    pub(crate) fn record_probe(&self, addr: &str, healthy: bool) {
+        if healthy {
+            self.record_success(addr, true);
+        } else {
+            self.record_failure(addr);
+        }
+    }
+
+    /// Forget the peers not in `peers`, so a removed peer stays ejected no
+    /// longer.
+    pub(crate) fn retain_peers(&self, peers: &[String]) {
+        self.peers.retain(|addr, _| peers.contains(addr));
+    }
+
+    pub(crate) fn peer_health(&self, addr: &str) -> PeerHealth {
+        let (healthy, consecutive_failures) = self.peers.get(addr).map_or((true, 0), |stats| {
+            (!stats.ejected, stats.consecutive_failures)
+        });
+        PeerHealth {
+            addr: addr.to_string(),
+            healthy,
+            consecutive_failures,
+        }
+    }
+
+    fn record_success(&self, addr: &str, readmit: bool) {
+        if self.config.is_none() {
+            return;
+        }
+        if let Some(mut stats) = self.peers.get_mut(addr) {
+            stats.consecutive_failures = 0;
+            if readmit {
+                stats.ejected = false;
+            }
+        }
+    }
+
+    fn record_failure(&self, addr: &str) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        let mut stats = self.peers.entry(addr.to_string()).or_default();
+        stats.consecutive_failures = stats.consecutive_failures.saturating_add(1);
+        if stats.consecutive_failures >= config.failure_threshold {
+            stats.ejected = true;
+        }
+    }
+}
+
+fn is_peer_failure(e: &Error) -> bool {
+    match e {
+        Error::Server { status, .. } => {
+            matches!(status.code(), Code::Unavailable | Code::DeadlineExceeded)
+        }
+        Error::CreateChannel { .. } => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Status;
+
+    use super::*;
+
+    #[test]
+    fn test_eject_and_readmit() {
+        let tracker = HealthTracker::new(Some(HealthCheckConfig::new().failure_threshold(2)));
+        let addr = "127.0.0.1:3001";
+        let unavailable: Result<(), Error> = Err(Status::unavailable("down").into());
+
+        assert!(tracker.is_healthy(addr));
+        tracker.record_request(addr, &unavailable);
+        assert!(tracker.is_healthy(addr));
+        assert!(!tracker.has_ejected());
+
+        tracker.record_probe(addr, false);
+        assert!(!tracker.is_healthy(addr));
+        assert!(tracker.has_ejected());
+        assert_eq!(
+            PeerHealth {
+                addr: addr.to_string(),
+                healthy: false,
+                consecutive_failures: 2,
+            },
+            tracker.peer_health(addr)
+        );
+
+        // a successful request does not re-admit an ejected peer
+        tracker.record_request(addr, &Ok::<(), Error>(()));
+        assert!(!tracker.is_healthy(addr));
+
+        tracker.record_probe(addr, true);
+        assert!(tracker.is_healthy(addr));
+        assert_eq!(0, tracker.peer_health(addr).consecutive_failures);
+    }
+
+    #[test]
+    fn test_retain_peers() {
+        let tracker = HealthTracker::new(Some(HealthCheckConfig::new().failure_threshold(1)));
+        let kept = "127.0.0.1:3001".to_string();
+        let removed = "127.0.0.1:3002".to_string();
+
+        tracker.record_probe(&kept, false);
+        tracker.record_probe(&removed, false);
+        tracker.retain_peers(std::slice::from_ref(&kept));
+        assert!(!tracker.is_healthy(&kept));
+        assert!(tracker.is_healthy(&removed));
+
+        tracker.record_probe(&kept, true);
+        assert!(!tracker.has_ejected());
+    }
+
+    #[test]
+    fn test_ignore_non_peer_failures() {
+        let tracker = HealthTracker::new(Some(HealthCheckConfig::new().failure_threshold(1)));
+        let addr = "127.0.0.1:3001";
+        let invalid: Result<(), Error> = Err(Status::invalid_argument("bad").into());
+
+        tracker.record_request(addr, &invalid);
+        assert!(tracker.is_healthy(addr));
+        assert_eq!(0, tracker.peer_health(addr).consecutive_failures);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let tracker = HealthTracker::default();
+        let addr = "127.0.0.1:3001";
+
+        for _ in 0..10 {
+            tracker.record_probe(addr, false);
+        }
+        assert!(tracker.is_healthy(addr));
+        assert!(!tracker.has_ejected());
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::api::v1::alter_expr::Kind;
+use crate::api::v1::*;
+use crate::error::{InvalidTableDefinitionSnafu, Result};
+
+/// Builder of a [`CreateTableExpr`], with columns declared by
+/// [`tag`](super::schema::tag), [`field`](super::schema::field) and
+/// [`timestamp`](super::schema::timestamp).
+///
+/// The time index is the timestamp column, and the primary keys default to
+/// the tag columns in declaration order.
+///
+/// ```ignore
+/// let expr = CreateTableBuilder::new("weather")
+///     .column(timestamp("ts", ColumnDataType::TimestampMillisecond))
+///     .column(tag("collector", ColumnDataType::String))
+///     .column(field("temperature", ColumnDataType::Float32))
+///     .table_option("ttl", "7d")
+///     .create_if_not_exists(true)
+///     .build()?;
+/// database.create_table(expr).await?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CreateTableBuilder {
+    table_name: String,
+    desc: String,
+    columns: Vec<ColumnSchema>,
+    primary_keys: Option<Vec<String>>,
+    create_if_not_exists: bool,
+    table_options: HashMap<String, String>,
+    engine: String,
+}
+
+impl CreateTableBuilder {
+    pub fn new(table_name: impl Into<String>) -> Self {
+        Self {
+            table_name: table_name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn desc(mut self, desc: impl Into<String>) -> Self {
+        self.desc = desc.into();
+        self
+    }
+
+    pub fn column(mut self, column: ColumnSchema) -> Self {
+        self.columns.push(column);
+        self
+    }
+
+    pub fn columns(mut self, columns: impl IntoIterator<Item = ColumnSchema>) -> Self {
+        self.columns.extend(columns);
+        self
+    }
+
+    /// Override the primary keys, which default to all tag columns.
+    pub fn primary_keys<S: AsRef<str>>(mut self, primary_keys: &[S]) -> Self {
+        self.primary_keys = Some(
+            primary_keys
+                .iter()
+                .map(|key| key.as_ref().to_string())
+                .collect(),
+        );
+        self
+    }
+
+    pub fn create_if_not_exists(mut self, create_if_not_exists: bool) -> Self {
+        self.create_if_not_exists = create_if_not_exists;
+        self
+    }
+
+    /// Set a table option, e.g. `ttl`.
+    pub fn table_option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.table_options.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the table engine, the server default is used if not set.
+    pub fn engine(mut self, engine: impl Into<String>) -> Self {
+        self.engine = engine.into();
+        self
+    }
+
+    pub fn build(self) -> Result<CreateTableExpr> {
+        let mut time_index = None;
+        for column in &self.columns {
+            if column.semantic_type != SemanticType::Timestamp as i32 {
+                continue;
+            }
+            if let Some(existing) = time_index.replace(column.column_name.clone()) {
+                return InvalidTableDefinitionSnafu {
+                    reason: format!(
+                        "multiple timestamp columns: {}, {}",
+                        existing, column.column_name
+                    ),
+                }
+                .fail();
+            }
+        }
+        let Some(time_index) = time_index else {
+            return InvalidTableDefinitionSnafu {
+                reason: "missing timestamp column",
+            }
+            .fail();
+        };
+
+        let primary_keys = match self.primary_keys {
+            Some(primary_keys) => {
+                if let Some(key) = primary_keys
+                    .iter()
+                    .find(|key| !self.columns.iter().any(|c| &c.column_name == *key))
+                {
+                    return InvalidTableDefinitionSnafu {
+                        reason: format!("primary key {key} is not a column"),
+                    }
+                    .fail();
+                }
+                primary_keys
+            }
+            None => self
+                .columns
+                .iter()
+                .filter(|c| c.semantic_type == SemanticType::Tag as i32)
+                .map(|c| c.column_name.clone())
+                .collect(),
+        };
+
+        Ok(CreateTableExpr {
+            table_name: self.table_name,
+            desc: self.desc,
+            column_defs: self.columns.into_iter().map(column_def).collect(),
+            time_index,
+            primary_keys,
+            create_if_not_exists: self.create_if_not_exists,
+            table_options: self.table_options,
+            engine: self.engine,
+            ..Default::default()
+        })
+    }
+}
+
+/// Build an [`AlterExpr`] that adds nullable columns to a table.
+pub fn add_columns(table_name: &str, columns: impl IntoIterator<Item = ColumnSchema>) -> AlterExpr {
+    let add_columns = columns
+        .into_iter()
+        .map(|column| AddColumn {
+            column_def: Some(column_def(column)),
+            location: None,
+        })
+        .collect();
+
+    alter_expr(table_name, Kind::AddColumns(AddColumns { add_columns }))
+}
+
+/// Build an [`AlterExpr`] that drops columns from a table.
+pub fn drop_columns<S: AsRef<str>>(table_name: &str, columns: &[S]) -> AlterExpr {
+    let drop_columns = columns
+        .iter()
+        .map(|name| DropColumn {
+            name: name.as_ref().to_string(),
+        })
+        .collect();
+
+    alter_expr(table_name, Kind::DropColumns(DropColumns { drop_columns }))
+}
+
+/// Build an [`AlterExpr`] that renames a table.
+pub fn rename_table(table_name: &str, new_table_name: &str) -> AlterExpr {
+    alter_expr(
+        table_name,
+        Kind::RenameTable(RenameTable {
+            new_table_name: new_table_name.to_string(),
+        }),
+    )
+}
+
+fn alter_expr(table_name: &str, kind: Kind) -> AlterExpr {
+    AlterExpr {
+        table_name: table_name.to_string(),
+        kind: Some(kind),
+        ..Default::default()
+    }
+}
+
+/// Timestamp columns are the time index and cannot be null, other columns are
+/// nullable.
+fn column_def(column: ColumnSchema) -> ColumnDef {
+    ColumnDef {
+        name: column.column_name,
+        data_type: column.datatype,
+        is_nullable: column.semantic_type != SemanticType::Timestamp as i32,
+        semantic_type: column.semantic_type,
+        datatype_extension: column.datatype_extension,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::schema::{field, tag, timestamp};
+
+    #[test]
+    fn test_create_table() {
+        let expr = CreateTableBuilder::new("weather")
+            .column(timestamp("ts", ColumnDataType::TimestampMillisecond))
+            .column(tag("collector", ColumnDataType::String))
+            .column(tag("region", ColumnDataType::String))
+            .column(field("temperature", ColumnDataType::Float32))
+            .table_option("ttl", "7d")
+            .create_if_not_exists(true)
+            .build()
+            .unwrap();
+
+        assert_eq!("weather", expr.table_name);
+        assert_eq!("ts", expr.time_index);
+        assert_eq!(vec!["collector", "region"], expr.primary_keys);
+        assert!(expr.create_if_not_exists);
+        assert_eq!("7d", expr.table_options["ttl"]);
+        assert_eq!(4, expr.column_defs.len());
+        assert!(!expr.column_defs[0].is_nullable);
+        assert!(expr.column_defs[3].is_nullable);
+        assert_eq!(
+            ColumnDataType::Float32 as i32,
+            expr.column_defs[3].data_type
+        );
+        assert_eq!(
+            SemanticType::Field as i32,
+            expr.column_defs[3].semantic_type
+        );
+
+        let expr = CreateTableBuilder::new("weather")
+            .column(timestamp("ts", ColumnDataType::TimestampMillisecond))
+            .column(tag("collector", ColumnDataType::String))
+            .column(tag("region", ColumnDataType::String))
+            .primary_keys(&["region"])
+            .build()
+            .unwrap();
+        assert_eq!(vec!["region"], expr.primary_keys);
+    }
+
+    #[test]
+    fn test_create_table_invalid() {
+        assert!(CreateTableBuilder::new("t")
+            .column(tag("host", ColumnDataType::String))
+            .build()
+            .is_err());
+
+        assert!(CreateTableBuilder::new("t")
+            .column(timestamp("ts", ColumnDataType::TimestampMillisecond))
+            .column(timestamp("ts2", ColumnDataType::TimestampMillisecond))
+            .build()
+            .is_err());
+
+        assert!(CreateTableBuilder::new("t")
+            .column(timestamp("ts", ColumnDataType::TimestampMillisecond))
+            .primary_keys(&["host"])
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_alter_table() {
+        let expr = add_columns("t", vec![field("cpu", ColumnDataType::Float64)]);
+        assert_eq!("t", expr.table_name);
+        let Some(Kind::AddColumns(AddColumns { add_columns })) = expr.kind else {
+            panic!("expect add columns");
+        };
+        assert_eq!("cpu", add_columns[0].column_def.as_ref().unwrap().name);
+
+        let expr = drop_columns("t", &["cpu", "memory"]);
+        let Some(Kind::DropColumns(DropColumns { drop_columns })) = expr.kind else {
+            panic!("expect drop columns");
+        };
+        assert_eq!(2, drop_columns.len());
+
+        let expr = rename_table("t", "t2");
+        assert!(matches!(
+            expr.kind,
+            Some(Kind::RenameTable(RenameTable { new_table_name })) if new_table_name == "t2"
+        ));
+    }
+}
@@ -12,5 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod ddl;
+pub mod registry;
+pub mod row;
 pub mod schema;
+#[cfg(feature = "serde")]
+pub mod serializer;
+pub mod validate;
 pub mod values;
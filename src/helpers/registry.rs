@@ -0,0 +1,367 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use snafu::ensure;
+
+use crate::api::v1::*;
+use crate::error::{ColumnSchemaConflictSnafu, InvalidSchemaSnafu, Result};
+use crate::helpers::schema::{field, tag, timestamp};
+use crate::helpers::values::none_value;
+
+/// Tracks the schemas of tables whose rows gain columns over time, and
+/// collects rows of them into well-formed [`RowInsertRequests`].
+///
+/// The schema of a table grows as rows bring new columns, and is kept across
+/// [`flush`](Self::flush)es. Columns missing from a row are null. A column
+/// must keep its datatype and semantic type in all rows.
+///
+/// ```ignore
+/// let mut registry = SchemaRegistry::new();
+/// registry.add_row(
+///     "sensor",
+///     SparseRow::new()
+///         .tag("device", ColumnDataType::String, string_value("d1".into()))
+///         .timestamp("ts", ColumnDataType::TimestampMillisecond, timestamp_millisecond_value(ts))
+///         .field("temperature", ColumnDataType::Float64, f64_value(21.5)),
+/// )?;
+/// database.row_insert(registry.flush()).await?;
+/// ```
+#[derive(Debug, Default)]
+pub struct SchemaRegistry {
+    tables: Vec<TableSchema>,
+    index: HashMap<String, usize>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The table named `name`, registered with an empty schema if unknown.
+    pub fn table(&mut self, name: &str) -> &mut TableSchema {
+        let index = match self.index.get(name) {
+            Some(index) => *index,
+            None => {
+                self.tables.push(TableSchema::new(name));
+                self.index.insert(name.to_string(), self.tables.len() - 1);
+                self.tables.len() - 1
+            }
+        };
+        &mut self.tables[index]
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TableSchema> {
+        self.index.get(name).map(|index| &self.tables[*index])
+    }
+
+    /// Add a row to the table named `name`, see [`TableSchema::add_row`].
+    pub fn add_row<I>(&mut self, name: &str, row: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (ColumnSchema, Value)>,
+    {
+        self.table(name).add_row(row)
+    }
+
+    /// Take the rows added since the last flush, one request per table with
+    /// rows, in the order the tables were registered.
+    pub fn flush(&mut self) -> RowInsertRequests {
+        RowInsertRequests {
+            inserts: self
+                .tables
+                .iter_mut()
+                .filter_map(TableSchema::flush)
+                .collect(),
+        }
+    }
+}
+
+/// The schema and pending rows of a table of a [`SchemaRegistry`].
+#[derive(Debug)]
+pub struct TableSchema {
+    name: String,
+    schema: Vec<ColumnSchema>,
+    columns: HashMap<String, usize>,
+    rows: Vec<Row>,
+}
+
+impl TableSchema {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            schema: Vec::new(),
+            columns: HashMap::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn schema(&self) -> &[ColumnSchema] {
+        &self.schema
+    }
+
+    /// Number of rows added since the last flush.
+    pub fn pending_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Add a column to the schema unless it is known, and get its index. A
+    /// table has at most one timestamp column.
+    pub fn add_column(&mut self, column: ColumnSchema) -> Result<usize> {
+        if let Some(&index) = self.columns.get(&column.column_name) {
+            self.check_column(&self.schema[index], &column)?;
+            return Ok(index);
+        }
+        self.check_timestamps(std::iter::once(&column))?;
+        let index = self.schema.len();
+        self.columns.insert(column.column_name.clone(), index);
+        self.schema.push(column);
+        Ok(index)
+    }
+
+    /// Add a row, adding its new columns to the schema. The row is rejected
+    /// as a whole if one of its columns conflicts with the schema.
+    pub fn add_row<I>(&mut self, row: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (ColumnSchema, Value)>,
+    {
+        let cells: Vec<_> = row.into_iter().collect();
+        let mut new_columns = HashMap::new();
+        for (column, _) in &cells {
+            let name = column.column_name.as_str();
+            match self.columns.get(name) {
+                Some(&index) => self.check_column(&self.schema[index], column)?,
+                None => {
+                    if let Some(existing) = new_columns.insert(name, column) {
+                        self.check_column(existing, column)?;
+                    }
+                }
+            }
+        }
+        self.check_timestamps(new_columns.values().copied())?;
+
+        let mut values = vec![none_value(); self.schema.len() + new_columns.len()];
+        for (column, value) in cells {
+            let index = self.add_column(column)?;
+            values[index] = value;
+        }
+        self.rows.push(Row { values });
+        Ok(())
+    }
+
+    /// Take the pending rows with the current schema, `None` if there are
+    /// none. Rows added before the schema grew are filled with nulls.
+    pub fn flush(&mut self) -> Option<RowInsertRequest> {
+        if self.rows.is_empty() {
+            return None;
+        }
+        let columns = self.schema.len();
+        let mut rows = std::mem::take(&mut self.rows);
+        for row in &mut rows {
+            row.values.resize(columns, none_value());
+        }
+        Some(RowInsertRequest {
+            table_name: self.name.clone(),
+            rows: Some(Rows {
+                schema: self.schema.clone(),
+                rows,
+            }),
+        })
+    }
+
+    fn check_column(&self, existing: &ColumnSchema, column: &ColumnSchema) -> Result<()> {
+        ensure!(
+            existing.datatype == column.datatype && existing.semantic_type == column.semantic_type,
+            ColumnSchemaConflictSnafu {
+                table: &self.name,
+                column: &column.column_name,
+            }
+        );
+        Ok(())
+    }
+
+    fn check_timestamps<'a>(
+        &self,
+        new_columns: impl Iterator<Item = &'a ColumnSchema>,
+    ) -> Result<()> {
+        let is_timestamp =
+            |column: &&ColumnSchema| column.semantic_type == SemanticType::Timestamp as i32;
+        let timestamps = self.schema.iter().filter(is_timestamp).count()
+            + new_columns.filter(is_timestamp).count();
+        ensure!(
+            timestamps <= 1,
+            InvalidSchemaSnafu {
+                table: &self.name,
+                reason: format!("expect exactly one timestamp column, found {timestamps}"),
+            }
+        );
+        Ok(())
+    }
+}
+
+/// A row of a [`SchemaRegistry`] table, holding only the columns it has
+/// values of.
+#[derive(Clone, Debug, Default)]
+pub struct SparseRow {
+    cells: Vec<(ColumnSchema, Value)>,
+}
+
+impl SparseRow {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn tag(self, name: &str, datatype: ColumnDataType, value: Value) -> Self {
+        self.column(tag(name, datatype), value)
+    }
+
+    pub fn timestamp(self, name: &str, datatype: ColumnDataType, value: Value) -> Self {
+        self.column(timestamp(name, datatype), value)
+    }
+
+    pub fn field(self, name: &str, datatype: ColumnDataType, value: Value) -> Self {
+        self.column(field(name, datatype), value)
+    }
+
+    pub fn column(mut self, column: ColumnSchema, value: Value) -> Self {
+        self.cells.push((column, value));
+        self
+    }
+}
+
+impl IntoIterator for SparseRow {
+    type Item = (ColumnSchema, Value);
+    type IntoIter = std::vec::IntoIter<(ColumnSchema, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cells.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::values::{f64_value, i64_value, string_value};
+    use crate::Error;
+
+    #[test]
+    fn test_merge_schema() {
+        let mut registry = SchemaRegistry::new();
+        registry
+            .add_row(
+                "cpu",
+                SparseRow::new().field("usage", ColumnDataType::Float64, f64_value(1.0)),
+            )
+            .unwrap();
+        registry
+            .add_row(
+                "cpu",
+                SparseRow::new()
+                    .tag("host", ColumnDataType::String, string_value("h1".into()))
+                    .field("usage", ColumnDataType::Float64, f64_value(2.0)),
+            )
+            .unwrap();
+
+        let err = registry
+            .add_row(
+                "cpu",
+                SparseRow::new()
+                    .field("cores", ColumnDataType::Int64, i64_value(4))
+                    .tag("usage", ColumnDataType::String, none_value()),
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::ColumnSchemaConflict { column, .. } if column == "usage"));
+        // The rejected row adds no column.
+        assert_eq!(2, registry.get("cpu").unwrap().schema().len());
+
+        let requests = registry.flush();
+        assert_eq!(1, requests.inserts.len());
+        let rows = requests.inserts[0].rows.as_ref().unwrap();
+        assert_eq!(2, rows.schema.len());
+        assert_eq!(vec![f64_value(1.0), none_value()], rows.rows[0].values);
+        assert_eq!(
+            vec![f64_value(2.0), string_value("h1".into())],
+            rows.rows[1].values
+        );
+    }
+
+    #[test]
+    fn test_second_timestamp() {
+        let mut registry = SchemaRegistry::new();
+        let row = SparseRow::new()
+            .timestamp("ts", ColumnDataType::TimestampMillisecond, none_value())
+            .field("usage", ColumnDataType::Float64, f64_value(1.0));
+        registry.add_row("cpu", row).unwrap();
+
+        let err = registry
+            .add_row(
+                "cpu",
+                SparseRow::new()
+                    .field("cores", ColumnDataType::Int64, i64_value(4))
+                    .timestamp("ts2", ColumnDataType::TimestampSecond, none_value()),
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidSchema { .. }), "{err:?}");
+        // The rejected row adds no column.
+        assert_eq!(2, registry.get("cpu").unwrap().schema().len());
+
+        let table = registry.table("cpu");
+        let err = table
+            .add_column(timestamp("ts2", ColumnDataType::TimestampSecond))
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidSchema { .. }), "{err:?}");
+        assert_eq!(1, table.pending_rows());
+    }
+
+    #[test]
+    fn test_schema_kept_across_flushes() {
+        let mut registry = SchemaRegistry::new();
+        registry
+            .add_row(
+                "cpu",
+                SparseRow::new()
+                    .tag("host", ColumnDataType::String, string_value("h1".into()))
+                    .field("usage", ColumnDataType::Float64, f64_value(1.0)),
+            )
+            .unwrap();
+        registry.flush();
+        assert!(registry.flush().inserts.is_empty());
+
+        registry
+            .add_row(
+                "cpu",
+                SparseRow::new().field("cores", ColumnDataType::Int64, i64_value(4)),
+            )
+            .unwrap();
+        assert!(registry
+            .add_row(
+                "cpu",
+                SparseRow::new().field("usage", ColumnDataType::Int64, i64_value(1)),
+            )
+            .is_err());
+
+        let requests = registry.flush();
+        let rows = requests.inserts[0].rows.as_ref().unwrap();
+        let names: Vec<_> = rows.schema.iter().map(|c| c.column_name.as_str()).collect();
+        assert_eq!(vec!["host", "usage", "cores"], names);
+        assert_eq!(
+            vec![none_value(), none_value(), i64_value(4)],
+            rows.rows[0].values
+        );
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::api::v1::{ColumnSchema, Row, RowInsertRequest, Rows};
+
+/// A type that maps to a row of a table. Enable the `derive` feature to derive
+/// it with `#[derive(GreptimeRow)]`.
+pub trait GreptimeRow {
+    /// Schema of the columns, in the order of the values of [`into_row`](Self::into_row)
+    fn schema() -> Vec<ColumnSchema>;
+
+    fn into_row(self) -> Row;
+}
+
+/// Bundle records into a [`RowInsertRequest`] of `table_name`.
+pub fn to_insert_request<T, I>(table_name: impl Into<String>, records: I) -> RowInsertRequest
+where
+    T: GreptimeRow,
+    I: IntoIterator<Item = T>,
+{
+    RowInsertRequest {
+        table_name: table_name.into(),
+        rows: Some(Rows {
+            schema: T::schema(),
+            rows: records.into_iter().map(GreptimeRow::into_row).collect(),
+        }),
+    }
+}
+
+/// Attributes rejected by `#[derive(GreptimeRow)]`.
+///
+/// An unknown attribute:
+///
+/// ```compile_fail
+/// #[derive(greptimedb_ingester::GreptimeRow)]
+/// struct Cpu {
+///     #[greptime(index)]
+///     host: String,
+///     #[greptime(timestamp = "ms")]
+///     ts: i64,
+/// }
+/// ```
+///
+/// More than one semantic type:
+///
+/// ```compile_fail
+/// #[derive(greptimedb_ingester::GreptimeRow)]
+/// struct Cpu {
+///     #[greptime(tag, field)]
+///     host: String,
+///     #[greptime(timestamp = "ms")]
+///     ts: i64,
+/// }
+/// ```
+///
+/// An unknown timestamp unit:
+///
+/// ```compile_fail
+/// #[derive(greptimedb_ingester::GreptimeRow)]
+/// struct Cpu {
+///     #[greptime(timestamp = "min")]
+///     ts: i64,
+/// }
+/// ```
+///
+/// A timestamp not of type `i64`:
+///
+/// ```compile_fail
+/// #[derive(greptimedb_ingester::GreptimeRow)]
+/// struct Cpu {
+///     #[greptime(timestamp = "ms")]
+///     ts: Option<i64>,
+/// }
+/// ```
+///
+/// No timestamp:
+///
+/// ```compile_fail
+/// #[derive(greptimedb_ingester::GreptimeRow)]
+/// struct Cpu {
+///     #[greptime(tag)]
+///     host: String,
+/// }
+/// ```
+#[cfg(all(doctest, feature = "derive"))]
+pub struct RejectedAttributes;
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+    use crate::api::v1::ColumnDataType;
+    use crate::helpers::schema::{field, tag, timestamp};
+    use crate::helpers::values::{
+        f64_value, none_value, string_value, timestamp_millisecond_value, u32_value,
+    };
+
+    #[derive(crate::GreptimeRow)]
+    struct Cpu {
+        #[greptime(tag, name = "host_name")]
+        host: String,
+        #[greptime(timestamp = "ms")]
+        ts: i64,
+        usage: Option<f64>,
+        #[greptime(field)]
+        cores: u32,
+    }
+
+    #[test]
+    fn test_derive() {
+        assert_eq!(
+            vec![
+                tag("host_name", ColumnDataType::String),
+                timestamp("ts", ColumnDataType::TimestampMillisecond),
+                field("usage", ColumnDataType::Float64),
+                field("cores", ColumnDataType::Uint32),
+            ],
+            Cpu::schema()
+        );
+
+        let cpu = |usage| Cpu {
+            host: "h1".to_string(),
+            ts: 1,
+            usage,
+            cores: 4,
+        };
+        assert_eq!(
+            vec![
+                string_value("h1".to_string()),
+                timestamp_millisecond_value(1),
+                f64_value(0.5),
+                u32_value(4),
+            ],
+            cpu(Some(0.5)).into_row().values
+        );
+        assert_eq!(none_value(), cpu(None).into_row().values[2]);
+
+        let request = to_insert_request("cpu", vec![cpu(Some(0.5)), cpu(None)]);
+        let rows = request.rows.unwrap();
+        assert_eq!(Cpu::schema(), rows.schema);
+        assert_eq!(2, rows.rows.len());
+    }
+}
@@ -0,0 +1,799 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Convert any [`Serialize`] struct or map into a [`Row`], matching its fields
+//! to columns by name.
+//!
+//! ```ignore
+//! #[derive(Serialize)]
+//! struct Cpu {
+//!     host: String,
+//!     ts: i64,
+//!     usage: Option<f64>,
+//! }
+//!
+//! let schema = vec![
+//!     tag("host", ColumnDataType::String),
+//!     timestamp("ts", ColumnDataType::TimestampMillisecond),
+//!     field("usage", ColumnDataType::Float64),
+//! ];
+//! let row = to_row(&cpu, &schema)?;
+//! ```
+
+use serde::ser::{self, Impossible, Serialize};
+use snafu::OptionExt;
+
+use crate::api::v1::column_data_type_extension::TypeExt;
+use crate::api::v1::{
+    ColumnDataType, ColumnDataTypeExtension, ColumnSchema, Row, Rows, SemanticType, Value,
+};
+use crate::error::{
+    ColumnTypeMismatchSnafu, Error, Result, SerializeRowSnafu, UnknownColumnDataTypeSnafu,
+};
+use crate::helpers::values::*;
+
+// The scale of decimal columns created without one, as in GreptimeDB.
+const DEFAULT_DECIMAL_SCALE: i32 = 10;
+
+/// Serialize `value` into a row of `schema`. Columns missing from `value` are
+/// null, fields not in `schema` are an error.
+pub fn to_row<T: Serialize + ?Sized>(value: &T, schema: &[ColumnSchema]) -> Result<Row> {
+    let mut values = vec![none_value(); schema.len()];
+    for (name, scalar) in value.serialize(RowSerializer)? {
+        let Some(index) = schema.iter().position(|c| c.column_name == name) else {
+            return SerializeRowSnafu {
+                reason: format!("column {name} is not in the schema"),
+            }
+            .fail();
+        };
+        values[index] = scalar.into_value(&schema[index])?;
+    }
+    Ok(Row { values })
+}
+
+/// Serialize `values` into rows of `schema`.
+pub fn to_rows<'a, T, I>(values: I, schema: Vec<ColumnSchema>) -> Result<Rows>
+where
+    T: Serialize + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    let rows = values
+        .into_iter()
+        .map(|value| to_row(value, &schema))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Rows { schema, rows })
+}
+
+/// Infer the schema of `value` from the types of its fields, in serialization
+/// order. All columns are fields, set the `semantic_type` of the tag and
+/// timestamp columns before use. Fields that are `None` cannot be inferred.
+pub fn infer_schema<T: Serialize + ?Sized>(value: &T) -> Result<Vec<ColumnSchema>> {
+    value
+        .serialize(RowSerializer)?
+        .into_iter()
+        .map(|(name, scalar)| {
+            let datatype = scalar.datatype().ok_or_else(|| {
+                SerializeRowSnafu {
+                    reason: format!("cannot infer the type of null column {name}"),
+                }
+                .build()
+            })?;
+            Ok(ColumnSchema {
+                column_name: name,
+                datatype: datatype as i32,
+                semantic_type: SemanticType::Field as i32,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        SerializeRowSnafu {
+            reason: msg.to_string(),
+        }
+        .build()
+    }
+}
+
+/// A serialized field value, before conversion to the datatype of its column.
+#[derive(Debug, Clone, PartialEq)]
+enum Scalar {
+    Null,
+    Bool(bool),
+    Int(i64, ColumnDataType),
+    UInt(u64, ColumnDataType),
+    F32(f32),
+    F64(f64),
+    String(String),
+    Binary(Vec<u8>),
+}
+
+impl Scalar {
+    /// The natural datatype of the scalar, used for schema inference.
+    fn datatype(&self) -> Option<ColumnDataType> {
+        match self {
+            Scalar::Null => None,
+            Scalar::Bool(_) => Some(ColumnDataType::Boolean),
+            Scalar::Int(_, datatype) | Scalar::UInt(_, datatype) => Some(*datatype),
+            Scalar::F32(_) => Some(ColumnDataType::Float32),
+            Scalar::F64(_) => Some(ColumnDataType::Float64),
+            Scalar::String(_) => Some(ColumnDataType::String),
+            Scalar::Binary(_) => Some(ColumnDataType::Binary),
+        }
+    }
+
+    fn into_value(self, column: &ColumnSchema) -> Result<Value> {
+        let datatype =
+            ColumnDataType::try_from(column.datatype)
+                .ok()
+                .context(UnknownColumnDataTypeSnafu {
+                    datatype: column.datatype,
+                })?;
+
+        let described = self.describe();
+        let value = match (self, datatype) {
+            (Scalar::Null, _) => Some(none_value()),
+            (Scalar::Bool(v), ColumnDataType::Boolean) => Some(bool_value(v)),
+            (Scalar::Int(v, _), ColumnDataType::Decimal128) => decimal_value(v as i128, column),
+            (Scalar::UInt(v, _), ColumnDataType::Decimal128) => decimal_value(v as i128, column),
+            (Scalar::Int(v, _), datatype) => integer_value(v as i128, datatype),
+            (Scalar::UInt(v, _), datatype) => integer_value(v as i128, datatype),
+            (Scalar::F32(v), ColumnDataType::Float32) => Some(f32_value(v)),
+            (Scalar::F32(v), ColumnDataType::Float64) => Some(f64_value(v as f64)),
+            (Scalar::F64(v), ColumnDataType::Float64) => Some(f64_value(v)),
+            (Scalar::String(v), ColumnDataType::String) => Some(string_value(v)),
+            (Scalar::Binary(v), ColumnDataType::Binary) => Some(binary_value(v)),
+            _ => None,
+        };
+
+        value.with_context(|| ColumnTypeMismatchSnafu {
+            column: &column.column_name,
+            datatype: format!("{datatype:?}"),
+            value: described,
+        })
+    }
+
+    /// The kind of the scalar, with its value if short, for error messages.
+    fn describe(&self) -> String {
+        match self {
+            Scalar::Null => "null".to_string(),
+            Scalar::Bool(v) => format!("bool {v}"),
+            Scalar::Int(v, _) => format!("integer {v}"),
+            Scalar::UInt(v, _) => format!("integer {v}"),
+            Scalar::F32(v) => format!("float {v}"),
+            Scalar::F64(v) => format!("float {v}"),
+            Scalar::String(_) => "string".to_string(),
+            Scalar::Binary(_) => "bytes".to_string(),
+        }
+    }
+}
+
+/// Convert an integer to an integer, float or temporal datatype, `None` if it
+/// is out of the range of `datatype` or `datatype` is not numeric.
+fn integer_value(v: i128, datatype: ColumnDataType) -> Option<Value> {
+    let value = match datatype {
+        ColumnDataType::Int8 => i8_value(v.try_into().ok()?),
+        ColumnDataType::Int16 => i16_value(v.try_into().ok()?),
+        ColumnDataType::Int32 => i32_value(v.try_into().ok()?),
+        ColumnDataType::Int64 => i64_value(v.try_into().ok()?),
+        ColumnDataType::Uint8 => u8_value(v.try_into().ok()?),
+        ColumnDataType::Uint16 => u16_value(v.try_into().ok()?),
+        ColumnDataType::Uint32 => u32_value(v.try_into().ok()?),
+        ColumnDataType::Uint64 => u64_value(v.try_into().ok()?),
+        ColumnDataType::Float32 => f32_value(v as f32),
+        ColumnDataType::Float64 => f64_value(v as f64),
+        ColumnDataType::Date => date_value(v.try_into().ok()?),
+        ColumnDataType::Datetime => datetime_value(v.try_into().ok()?),
+        ColumnDataType::TimestampSecond => timestamp_second_value(v.try_into().ok()?),
+        ColumnDataType::TimestampMillisecond => timestamp_millisecond_value(v.try_into().ok()?),
+        ColumnDataType::TimestampMicrosecond => timestamp_microsecond_value(v.try_into().ok()?),
+        ColumnDataType::TimestampNanosecond => timestamp_nanosecond_value(v.try_into().ok()?),
+        ColumnDataType::TimeSecond => time_second_value(v.try_into().ok()?),
+        ColumnDataType::TimeMillisecond => time_millisecond_value(v.try_into().ok()?),
+        ColumnDataType::TimeMicrosecond => time_microsecond_value(v.try_into().ok()?),
+        ColumnDataType::TimeNanosecond => time_nanosecond_value(v.try_into().ok()?),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Convert an integer to a decimal at the scale of `column`, `None` if it
+/// overflows.
+fn decimal_value(v: i128, column: &ColumnSchema) -> Option<Value> {
+    let scale = match &column.datatype_extension {
+        Some(ColumnDataTypeExtension {
+            type_ext: Some(TypeExt::DecimalType(decimal)),
+        }) => decimal.scale,
+        _ => DEFAULT_DECIMAL_SCALE,
+    };
+    let factor = 10i128.checked_pow(scale.try_into().ok()?)?;
+    Some(decimal128_value(v.checked_mul(factor)?))
+}
+
+fn unsupported<T>(what: &str) -> Result<T> {
+    SerializeRowSnafu {
+        reason: format!("{what} is not supported as a column value"),
+    }
+    .fail()
+}
+
+/// Serializes a struct or a map with string keys into named scalars.
+struct RowSerializer;
+
+struct RowFields {
+    fields: Vec<(String, Scalar)>,
+    key: Option<String>,
+}
+
+impl ser::Serializer for RowSerializer {
+    type Ok = Vec<(String, Scalar)>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Self::Ok, Error>;
+    type SerializeTuple = Impossible<Self::Ok, Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Error>;
+    type SerializeMap = RowFields;
+    type SerializeStruct = RowFields;
+    type SerializeStructVariant = Impossible<Self::Ok, Error>;
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<RowFields> {
+        Ok(RowFields {
+            fields: Vec::with_capacity(len),
+            key: None,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<RowFields> {
+        Ok(RowFields {
+            fields: Vec::with_capacity(len.unwrap_or_default()),
+            key: None,
+        })
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<Self::Ok> {
+        unsupported("bool as a row")
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Self::Ok> {
+        unsupported("integer as a row")
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Self::Ok> {
+        unsupported("integer as a row")
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Self::Ok> {
+        unsupported("integer as a row")
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Self::Ok> {
+        unsupported("integer as a row")
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Self::Ok> {
+        unsupported("integer as a row")
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Self::Ok> {
+        unsupported("integer as a row")
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Self::Ok> {
+        unsupported("integer as a row")
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Self::Ok> {
+        unsupported("integer as a row")
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok> {
+        unsupported("float as a row")
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok> {
+        unsupported("float as a row")
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Self::Ok> {
+        unsupported("char as a row")
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Self::Ok> {
+        unsupported("string as a row")
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
+        unsupported("bytes as a row")
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        unsupported("none as a row")
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        unsupported("unit as a row")
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        unsupported("unit struct as a row")
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok> {
+        unsupported("enum as a row")
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok> {
+        unsupported("enum as a row")
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        unsupported("sequence as a row")
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        unsupported("tuple as a row")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        unsupported("tuple struct as a row")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        unsupported("enum as a row")
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        unsupported("enum as a row")
+    }
+}
+
+impl ser::SerializeStruct for RowFields {
+    type Ok = Vec<(String, Scalar)>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let scalar = value.serialize(ScalarSerializer)?;
+        self.fields.push((key.to_string(), scalar));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(self.fields)
+    }
+}
+
+impl ser::SerializeMap for RowFields {
+    type Ok = Vec<(String, Scalar)>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        let Scalar::String(key) = key.serialize(ScalarSerializer)? else {
+            return unsupported("non-string map key");
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let Some(key) = self.key.take() else {
+            return unsupported("map value without key");
+        };
+        let scalar = value.serialize(ScalarSerializer)?;
+        self.fields.push((key, scalar));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(self.fields)
+    }
+}
+
+/// Serializes a single field value. Sequences are only accepted as bytes, to
+/// support `Vec<u8>` which serde serializes as a sequence of `u8`.
+struct ScalarSerializer;
+
+struct BytesSeq {
+    bytes: Vec<u8>,
+}
+
+impl ser::Serializer for ScalarSerializer {
+    type Ok = Scalar;
+    type Error = Error;
+    type SerializeSeq = BytesSeq;
+    type SerializeTuple = Impossible<Scalar, Error>;
+    type SerializeTupleStruct = Impossible<Scalar, Error>;
+    type SerializeTupleVariant = Impossible<Scalar, Error>;
+    type SerializeMap = Impossible<Scalar, Error>;
+    type SerializeStruct = Impossible<Scalar, Error>;
+    type SerializeStructVariant = Impossible<Scalar, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Scalar> {
+        Ok(Scalar::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Scalar> {
+        Ok(Scalar::Int(v as i64, ColumnDataType::Int8))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Scalar> {
+        Ok(Scalar::Int(v as i64, ColumnDataType::Int16))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Scalar> {
+        Ok(Scalar::Int(v as i64, ColumnDataType::Int32))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Scalar> {
+        Ok(Scalar::Int(v, ColumnDataType::Int64))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Scalar> {
+        Ok(Scalar::UInt(v as u64, ColumnDataType::Uint8))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Scalar> {
+        Ok(Scalar::UInt(v as u64, ColumnDataType::Uint16))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Scalar> {
+        Ok(Scalar::UInt(v as u64, ColumnDataType::Uint32))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Scalar> {
+        Ok(Scalar::UInt(v, ColumnDataType::Uint64))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Scalar> {
+        Ok(Scalar::F32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Scalar> {
+        Ok(Scalar::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Scalar> {
+        Ok(Scalar::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Scalar> {
+        Ok(Scalar::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Scalar> {
+        Ok(Scalar::Binary(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Scalar> {
+        Ok(Scalar::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Scalar> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Scalar> {
+        Ok(Scalar::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Scalar> {
+        Ok(Scalar::Null)
+    }
+
+    /// Unit enum variants are written as their names.
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Scalar> {
+        Ok(Scalar::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Scalar> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Scalar> {
+        unsupported("enum with data")
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<BytesSeq> {
+        Ok(BytesSeq {
+            bytes: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        unsupported("tuple")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        unsupported("tuple struct")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        unsupported("enum with data")
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        unsupported("nested map")
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        unsupported("nested struct")
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        unsupported("enum with data")
+    }
+}
+
+impl ser::SerializeSeq for BytesSeq {
+    type Ok = Scalar;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        match value.serialize(ScalarSerializer)? {
+            Scalar::UInt(v, ColumnDataType::Uint8) => {
+                self.bytes.push(v as u8);
+                Ok(())
+            }
+            _ => unsupported("sequence of non u8"),
+        }
+    }
+
+    fn end(self) -> Result<Scalar> {
+        Ok(Scalar::Binary(self.bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    use super::*;
+    use crate::api::v1::value::ValueData;
+    use crate::api::v1::DecimalTypeExtension;
+    use crate::helpers::schema::{field, tag, timestamp};
+
+    #[derive(Serialize)]
+    struct Cpu {
+        host: String,
+        ts: i64,
+        usage: Option<f64>,
+        cores: u8,
+        raw: Vec<u8>,
+        state: State,
+    }
+
+    #[derive(Serialize)]
+    enum State {
+        Running,
+    }
+
+    fn cpu_schema() -> Vec<ColumnSchema> {
+        vec![
+            tag("host", ColumnDataType::String),
+            timestamp("ts", ColumnDataType::TimestampMillisecond),
+            field("usage", ColumnDataType::Float64),
+            field("cores", ColumnDataType::Int32),
+            field("raw", ColumnDataType::Binary),
+            field("state", ColumnDataType::String),
+            field("missing", ColumnDataType::Float64),
+        ]
+    }
+
+    fn mock_cpu() -> Cpu {
+        Cpu {
+            host: "h1".to_string(),
+            ts: 1686109527000,
+            usage: None,
+            cores: 8,
+            raw: vec![1, 2],
+            state: State::Running,
+        }
+    }
+
+    #[test]
+    fn test_to_row() {
+        let row = to_row(&mock_cpu(), &cpu_schema()).unwrap();
+
+        assert_eq!(
+            vec![
+                string_value("h1".to_string()),
+                timestamp_millisecond_value(1686109527000),
+                none_value(),
+                i32_value(8),
+                binary_value(vec![1, 2]),
+                string_value("Running".to_string()),
+                none_value(),
+            ],
+            row.values
+        );
+    }
+
+    fn decimal_field(name: &str, scale: i32) -> ColumnSchema {
+        let mut column = field(name, ColumnDataType::Decimal128);
+        column.datatype_extension = Some(ColumnDataTypeExtension {
+            type_ext: Some(TypeExt::DecimalType(DecimalTypeExtension {
+                precision: 38,
+                scale,
+            })),
+        });
+        column
+    }
+
+    #[test]
+    fn test_to_row_decimal() {
+        let schema = vec![
+            decimal_field("price", 2),
+            field("cost", ColumnDataType::Decimal128),
+        ];
+
+        let mut map = BTreeMap::new();
+        map.insert("price", 5);
+        map.insert("cost", 5);
+        let row = to_row(&map, &schema).unwrap();
+        assert_eq!(
+            vec![decimal128_value(500), decimal128_value(50_000_000_000)],
+            row.values
+        );
+
+        // 5 * 10^38 does not fit in a decimal.
+        let mut schema = schema;
+        schema[1] = decimal_field("cost", 38);
+        let err = to_row(&map, &schema).unwrap_err();
+        assert!(matches!(&err, Error::ColumnTypeMismatch { column, .. } if column == "cost"));
+    }
+
+    #[test]
+    fn test_to_row_map() {
+        let mut map = BTreeMap::new();
+        map.insert("cores", 300);
+        let row = to_row(&map, &cpu_schema()[3..4]).unwrap();
+        assert_eq!(Some(ValueData::I32Value(300)), row.values[0].value_data);
+    }
+
+    #[test]
+    fn test_to_row_mismatch() {
+        let mut schema = cpu_schema();
+        schema[0] = tag("host", ColumnDataType::Int64);
+        let err = to_row(&mock_cpu(), &schema).unwrap_err();
+        assert!(matches!(&err, Error::ColumnTypeMismatch { column, .. } if column == "host"));
+        assert_eq!(
+            "Cannot write string into column host of type Int64",
+            err.to_string()
+        );
+
+        let mut schema = cpu_schema();
+        schema[3] = field("cores", ColumnDataType::Int8);
+        let mut map = BTreeMap::new();
+        map.insert("cores", 300);
+        let err = to_row(&map, &schema).unwrap_err();
+        assert!(matches!(&err, Error::ColumnTypeMismatch { column, .. } if column == "cores"));
+        assert_eq!(
+            "Cannot write integer 300 into column cores of type Int8",
+            err.to_string()
+        );
+
+        // An integer into a string column is a mismatch, not out of range.
+        schema[3] = field("cores", ColumnDataType::String);
+        let err = to_row(&map, &schema).unwrap_err();
+        assert_eq!(
+            "Cannot write integer 300 into column cores of type String",
+            err.to_string()
+        );
+
+        let err = to_row(&mock_cpu(), &cpu_schema()[..2]).unwrap_err();
+        assert!(matches!(err, Error::SerializeRow { .. }));
+
+        assert!(to_row(&1, &cpu_schema()).is_err());
+    }
+
+    #[test]
+    fn test_infer_schema() {
+        let mut cpu = mock_cpu();
+        assert!(infer_schema(&cpu).is_err());
+
+        cpu.usage = Some(0.5);
+        let schema = infer_schema(&cpu).unwrap();
+        assert_eq!(
+            vec![
+                field("host", ColumnDataType::String),
+                field("ts", ColumnDataType::Int64),
+                field("usage", ColumnDataType::Float64),
+                field("cores", ColumnDataType::Uint8),
+                field("raw", ColumnDataType::Binary),
+                field("state", ColumnDataType::String),
+            ],
+            schema
+        );
+
+        let rows = to_rows([&cpu], schema).unwrap();
+        assert_eq!(1, rows.rows.len());
+    }
+}
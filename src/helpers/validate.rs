@@ -0,0 +1,278 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::api::v1::value::ValueData;
+use crate::api::v1::*;
+use crate::error::{InvalidRowSnafu, InvalidSchemaSnafu, Result};
+
+/// Client side checks of requests, reporting the mistakes the server would
+/// otherwise reject with a less precise message.
+///
+/// ```ignore
+/// use greptimedb_ingester::helpers::validate::Validate;
+///
+/// requests.validate()?;
+/// database.row_insert(requests).await?;
+/// ```
+///
+/// [`Database::set_validate`](crate::Database::set_validate) runs it on every
+/// `row_insert`.
+pub trait Validate {
+    fn validate(&self) -> Result<()>;
+}
+
+impl Validate for RowInsertRequests {
+    fn validate(&self) -> Result<()> {
+        self.inserts.iter().try_for_each(Validate::validate)
+    }
+}
+
+/// Checks that the schema has unique column names, known datatypes and
+/// exactly one timestamp column, and that every row has a value of the
+/// datatype of each column, with a non-null timestamp.
+impl Validate for RowInsertRequest {
+    fn validate(&self) -> Result<()> {
+        let Some(rows) = &self.rows else {
+            return Ok(());
+        };
+        let table = &self.table_name;
+        let datatypes = validate_schema(table, &rows.schema)?;
+
+        for (row_index, row) in rows.rows.iter().enumerate() {
+            if row.values.len() != datatypes.len() {
+                return InvalidRowSnafu {
+                    table,
+                    row: row_index,
+                    column: None,
+                    reason: format!(
+                        "expect {} values, found {}",
+                        datatypes.len(),
+                        row.values.len()
+                    ),
+                }
+                .fail();
+            }
+
+            for (column_index, (value, (column, datatype))) in row
+                .values
+                .iter()
+                .zip(rows.schema.iter().zip(&datatypes))
+                .enumerate()
+            {
+                let reason = match &value.value_data {
+                    None if column.semantic_type == SemanticType::Timestamp as i32 => {
+                        format!(
+                            "null timestamp in column {} ({})",
+                            column_index, column.column_name
+                        )
+                    }
+                    Some(value) if !is_compatible(*datatype, value) => format!(
+                        "column {} ({}) of type {:?} cannot hold {:?}",
+                        column_index, column.column_name, datatype, value
+                    ),
+                    _ => continue,
+                };
+                return InvalidRowSnafu {
+                    table,
+                    row: row_index,
+                    column: Some(column_index),
+                    reason,
+                }
+                .fail();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validate the columns and get their datatypes.
+fn validate_schema(table: &str, schema: &[ColumnSchema]) -> Result<Vec<ColumnDataType>> {
+    let mut names = HashMap::with_capacity(schema.len());
+    let mut timestamps = 0;
+    let mut datatypes = Vec::with_capacity(schema.len());
+
+    for (index, column) in schema.iter().enumerate() {
+        if let Some(first) = names.insert(column.column_name.as_str(), index) {
+            return InvalidSchemaSnafu {
+                table,
+                reason: format!(
+                    "duplicate column {} at {} and {}",
+                    column.column_name, first, index
+                ),
+            }
+            .fail();
+        }
+        if column.semantic_type == SemanticType::Timestamp as i32 {
+            timestamps += 1;
+        }
+        let datatype = ColumnDataType::try_from(column.datatype).map_err(|_| {
+            InvalidSchemaSnafu {
+                table,
+                reason: format!(
+                    "unknown datatype {} of column {} ({})",
+                    column.datatype, index, column.column_name
+                ),
+            }
+            .build()
+        })?;
+        datatypes.push(datatype);
+    }
+
+    if timestamps != 1 {
+        return InvalidSchemaSnafu {
+            table,
+            reason: format!("expect exactly one timestamp column, found {timestamps}"),
+        }
+        .fail();
+    }
+    Ok(datatypes)
+}
+
+/// Whether a column of `datatype` accepts `value`.
+fn is_compatible(datatype: ColumnDataType, value: &ValueData) -> bool {
+    use ColumnDataType as T;
+    use ValueData as V;
+
+    matches!(
+        (datatype, value),
+        (T::Boolean, V::BoolValue(_))
+            | (T::Int8, V::I8Value(_))
+            | (T::Int16, V::I16Value(_))
+            | (T::Int32, V::I32Value(_))
+            | (T::Int64, V::I64Value(_))
+            | (T::Uint8, V::U8Value(_))
+            | (T::Uint16, V::U16Value(_))
+            | (T::Uint32, V::U32Value(_))
+            | (T::Uint64, V::U64Value(_))
+            | (T::Float32, V::F32Value(_))
+            | (T::Float64, V::F64Value(_))
+            | (T::Binary, V::BinaryValue(_))
+            | (T::String, V::StringValue(_))
+            | (T::Date, V::DateValue(_))
+            | (T::Datetime, V::DatetimeValue(_))
+            | (T::TimestampSecond, V::TimestampSecondValue(_))
+            | (T::TimestampMillisecond, V::TimestampMillisecondValue(_))
+            | (T::TimestampMicrosecond, V::TimestampMicrosecondValue(_))
+            | (T::TimestampNanosecond, V::TimestampNanosecondValue(_))
+            | (T::TimeSecond, V::TimeSecondValue(_))
+            | (T::TimeMillisecond, V::TimeMillisecondValue(_))
+            | (T::TimeMicrosecond, V::TimeMicrosecondValue(_))
+            | (T::TimeNanosecond, V::TimeNanosecondValue(_))
+            | (T::IntervalYearMonth, V::IntervalYearMonthValue(_))
+            | (T::IntervalDayTime, V::IntervalDayTimeValue(_))
+            | (T::IntervalMonthDayNano, V::IntervalMonthDayNanoValue(_))
+            | (T::DurationSecond, V::DurationSecondValue(_))
+            | (T::DurationMillisecond, V::DurationMillisecondValue(_))
+            | (T::DurationMicrosecond, V::DurationMicrosecondValue(_))
+            | (T::DurationNanosecond, V::DurationNanosecondValue(_))
+            | (T::Decimal128, V::Decimal128Value(_))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::schema::{field, tag, timestamp};
+    use crate::helpers::values::{
+        f64_value, i64_value, none_value, string_value, timestamp_millisecond_value,
+    };
+    use crate::Error;
+
+    fn request(schema: Vec<ColumnSchema>, rows: Vec<Vec<Value>>) -> RowInsertRequest {
+        RowInsertRequest {
+            table_name: "cpu".to_string(),
+            rows: Some(Rows {
+                schema,
+                rows: rows.into_iter().map(|values| Row { values }).collect(),
+            }),
+        }
+    }
+
+    fn schema() -> Vec<ColumnSchema> {
+        vec![
+            tag("host", ColumnDataType::String),
+            timestamp("ts", ColumnDataType::TimestampMillisecond),
+            field("usage", ColumnDataType::Float64),
+        ]
+    }
+
+    #[test]
+    fn test_validate_rows() {
+        let valid = vec![
+            string_value("a".to_string()),
+            timestamp_millisecond_value(1),
+            f64_value(0.5),
+        ];
+        let nullable = vec![none_value(), timestamp_millisecond_value(2), none_value()];
+        let requests = RowInsertRequests {
+            inserts: vec![request(schema(), vec![valid.clone(), nullable])],
+        };
+        requests.validate().unwrap();
+
+        let err = request(schema(), vec![valid.clone(), valid[..2].to_vec()])
+            .validate()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidRow {
+                row: 1,
+                column: None,
+                ..
+            }
+        ));
+
+        let err = request(
+            schema(),
+            vec![vec![valid[0].clone(), valid[1].clone(), i64_value(1)]],
+        )
+        .validate()
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidRow {
+                row: 0,
+                column: Some(2),
+                ..
+            }
+        ));
+
+        let err = request(
+            schema(),
+            vec![vec![valid[0].clone(), none_value(), valid[2].clone()]],
+        )
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("null timestamp in column 1 (ts)"));
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        let mut duplicate = schema();
+        duplicate.push(field("host", ColumnDataType::String));
+        let err = request(duplicate, vec![]).validate().unwrap_err();
+        assert!(err.to_string().contains("duplicate column host at 0 and 3"));
+
+        let mut no_timestamp = schema();
+        no_timestamp.remove(1);
+        let err = request(no_timestamp, vec![]).validate().unwrap_err();
+        assert!(matches!(err, Error::InvalidSchema { .. }));
+
+        let mut unknown = schema();
+        unknown[2].datatype = 1000;
+        let err = request(unknown, vec![]).validate().unwrap_err();
+        assert!(err.to_string().contains("unknown datatype 1000"));
+    }
+}
@@ -69,6 +69,10 @@ define_value_fn!(time_microsecond_value, i64, TimeMicrosecondValue);
 define_value_fn!(time_nanosecond_value, i64, TimeNanosecondValue);
 define_value_fn!(interval_year_month_value, i32, IntervalYearMonthValue);
 define_value_fn!(interval_day_time_value, i64, IntervalDayTimeValue);
+define_value_fn!(duration_second_value, i64, DurationSecondValue);
+define_value_fn!(duration_millisecond_value, i64, DurationMillisecondValue);
+define_value_fn!(duration_microsecond_value, i64, DurationMicrosecondValue);
+define_value_fn!(duration_nanosecond_value, i64, DurationNanosecondValue);
 
 #[inline]
 pub fn interval_month_day_nano_value(
@@ -0,0 +1,347 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration;
+
+use snafu::ensure;
+use tonic::metadata::{Ascii, MetadataValue};
+
+use crate::error::{Error, InvalidHintSnafu, Result};
+
+/// Metadata key of the hints of a request.
+pub(crate) const HINTS_KEY: &str = "x-greptime-hints";
+
+const TTL: &str = "ttl";
+const APPEND_MODE: &str = "append_mode";
+const MERGE_MODE: &str = "merge_mode";
+const AUTO_CREATE_TABLE: &str = "auto_create_table";
+const SKIP_WAL: &str = "skip_wal";
+const PHYSICAL_TABLE: &str = "physical_table";
+
+const KNOWN_KEYS: [&str; 6] = [
+    TTL,
+    APPEND_MODE,
+    MERGE_MODE,
+    AUTO_CREATE_TABLE,
+    SKIP_WAL,
+    PHYSICAL_TABLE,
+];
+
+/// Whether `key` is a hint with a typed setter.
+pub(crate) fn is_known_hint(key: &str) -> bool {
+    KNOWN_KEYS.contains(&key)
+}
+
+/// How rows with the same primary key and timestamp are merged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeMode {
+    /// Keep the last row
+    LastRow,
+    /// Keep the last non null value of every field
+    LastNonNull,
+}
+
+impl MergeMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergeMode::LastRow => "last_row",
+            MergeMode::LastNonNull => "last_non_null",
+        }
+    }
+}
+
+/// Hints sent as `x-greptime-hints` with a write, mostly options of the
+/// tables created by it.
+///
+/// Hints are validated before the request is sent. They can also be parsed
+/// from the header format, `key=value` pairs separated by commas, which
+/// only accepts the hints with a typed setter.
+///
+/// ```ignore
+/// let hints = Hints::new()
+///     .ttl(Duration::from_secs(7 * 24 * 3600))
+///     .append_mode(true);
+/// database.row_insert_with_hints(requests, &hints).await?;
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Hints {
+    hints: Vec<(String, String)>,
+}
+
+impl Hints {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Time to live of the rows of created tables.
+    pub fn ttl(self, ttl: Duration) -> Self {
+        self.set(TTL, format_duration(ttl))
+    }
+
+    /// Create tables in append mode, which keeps rows with the same primary
+    /// key and timestamp.
+    pub fn append_mode(self, append_mode: bool) -> Self {
+        self.set(APPEND_MODE, append_mode.to_string())
+    }
+
+    /// Merge mode of created tables.
+    pub fn merge_mode(self, merge_mode: MergeMode) -> Self {
+        self.set(MERGE_MODE, merge_mode.as_str())
+    }
+
+    /// Whether missing tables are created, the server default is true.
+    pub fn auto_create_table(self, auto_create_table: bool) -> Self {
+        self.set(AUTO_CREATE_TABLE, auto_create_table.to_string())
+    }
+
+    /// Skip the write ahead log of created tables.
+    pub fn skip_wal(self, skip_wal: bool) -> Self {
+        self.set(SKIP_WAL, skip_wal.to_string())
+    }
+
+    /// Create tables as logical tables of this physical table of the metric
+    /// engine.
+    pub fn physical_table(self, physical_table: impl Into<String>) -> Self {
+        self.set(PHYSICAL_TABLE, physical_table)
+    }
+
+    /// Set a hint without a typed setter, replacing the hint of the same key.
+    pub fn set(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        let value = value.into();
+        match self.hints.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.hints.push((key, value)),
+        }
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.hints.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+
+    /// Check the hints can be sent, and the values of known hints.
+    pub fn validate(&self) -> Result<()> {
+        for (key, value) in self.iter() {
+            validate_hint(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// The validated header value, or `None` if there are no hints.
+    pub(crate) fn to_metadata_value(&self) -> Result<Option<MetadataValue<Ascii>>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        self.validate()?;
+        // Validated hints are visible ASCII.
+        Ok(Some(MetadataValue::try_from(self.to_string()).unwrap()))
+    }
+}
+
+impl Display for Hints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (key, value)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{key}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Hints {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut hints = Hints::new();
+        for hint in s.split(',').map(str::trim).filter(|hint| !hint.is_empty()) {
+            let Some((key, value)) = hint.split_once('=') else {
+                return InvalidHintSnafu {
+                    hint,
+                    reason: "expect key=value",
+                }
+                .fail();
+            };
+            let (key, value) = (key.trim(), value.trim());
+            ensure!(
+                is_known_hint(key),
+                InvalidHintSnafu {
+                    hint,
+                    reason: "unknown hint",
+                }
+            );
+            validate_hint(key, value)?;
+            hints = hints.set(key, value);
+        }
+        Ok(hints)
+    }
+}
+
+fn validate_hint(key: &str, value: &str) -> Result<()> {
+    let hint = || format!("{key}={value}");
+    ensure!(
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')),
+        InvalidHintSnafu {
+            hint: hint(),
+            reason: "key must be ASCII letters, digits, '_', '.' or '-'",
+        }
+    );
+    ensure!(
+        !value.is_empty()
+            && value
+                .chars()
+                .all(|c| c.is_ascii_graphic() && c != ',' && c != '='),
+        InvalidHintSnafu {
+            hint: hint(),
+            reason: "value must be visible ASCII without ',' or '='",
+        }
+    );
+
+    let valid = match key {
+        TTL => is_duration(value),
+        APPEND_MODE | AUTO_CREATE_TABLE | SKIP_WAL => matches!(value, "true" | "false"),
+        MERGE_MODE => [MergeMode::LastRow, MergeMode::LastNonNull]
+            .iter()
+            .any(|mode| mode.as_str() == value),
+        _ => true,
+    };
+    ensure!(
+        valid,
+        InvalidHintSnafu {
+            hint: hint(),
+            reason: format!("invalid value of {key}"),
+        }
+    );
+    Ok(())
+}
+
+/// Format as the largest unit without loss, like `7d` or `90s`.
+fn format_duration(duration: Duration) -> String {
+    if duration.subsec_nanos() != 0 {
+        return format!("{}ms", duration.as_millis());
+    }
+    let secs = duration.as_secs();
+    for (unit, unit_secs) in [("d", 24 * 3600), ("h", 3600), ("m", 60)] {
+        if secs != 0 && secs.is_multiple_of(unit_secs) {
+            return format!("{}{unit}", secs / unit_secs);
+        }
+    }
+    format!("{secs}s")
+}
+
+/// Whether `value` is a ttl accepted by the server, like `forever` or
+/// `1h30m`.
+fn is_duration(value: &str) -> bool {
+    if matches!(value, "forever" | "instant") {
+        return true;
+    }
+
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return false;
+        }
+        rest = &rest[digits..];
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if !matches!(
+            &rest[..unit],
+            "ns" | "us" | "ms" | "s" | "m" | "h" | "d" | "w" | "M" | "y"
+        ) {
+            return false;
+        }
+        rest = &rest[unit..];
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hints_header() {
+        let hints = Hints::new()
+            .ttl(Duration::from_secs(7 * 24 * 3600))
+            .append_mode(true)
+            .merge_mode(MergeMode::LastNonNull)
+            .auto_create_table(false)
+            .skip_wal(true)
+            .physical_table("greptime_physical_table")
+            .ttl(Duration::from_secs(90));
+        assert_eq!(
+            "ttl=90s,append_mode=true,merge_mode=last_non_null,auto_create_table=false,\
+             skip_wal=true,physical_table=greptime_physical_table",
+            hints.to_string()
+        );
+        assert_eq!(Some("90s"), hints.get("ttl"));
+        assert_eq!(hints, hints.to_string().parse().unwrap());
+
+        assert!(Hints::new().to_metadata_value().unwrap().is_none());
+        let value = Hints::new()
+            .ttl(Duration::from_millis(1500))
+            .to_metadata_value()
+            .unwrap()
+            .unwrap();
+        assert_eq!("ttl=1500ms", value.to_str().unwrap());
+    }
+
+    #[test]
+    fn test_invalid_hints() {
+        for hints in [
+            "tll=7d",
+            "ttl",
+            "ttl=7 days",
+            "ttl=d",
+            "append_mode=yes",
+            "merge_mode=first_row",
+        ] {
+            assert!(
+                matches!(hints.parse::<Hints>(), Err(Error::InvalidHint { .. })),
+                "{hints}"
+            );
+        }
+        assert_eq!(
+            Hints::new().ttl(Duration::from_secs(3600)),
+            " ttl = 1h, ".parse().unwrap()
+        );
+
+        assert!(Hints::new().set("ttl", "7x").validate().is_err());
+        assert!(Hints::new().set("comment", "a,b").validate().is_err());
+        assert!(Hints::new().set("", "a").validate().is_err());
+        assert!(Hints::new()
+            .set("compaction.type", "twcs")
+            .validate()
+            .is_ok());
+    }
+}
@@ -13,18 +13,51 @@
 // limitations under the License.
 
 pub mod api;
+mod bulk_writer;
 pub mod channel_manager;
 mod client;
+mod connection_string;
 mod database;
 mod error;
+#[cfg(feature = "flight")]
+mod flight;
+pub mod formats;
+mod health;
 pub mod helpers;
+mod hints;
 pub mod load_balance;
+pub mod metrics;
+mod reconnect;
+mod request_options;
+mod retry;
+mod split;
+mod spool;
+mod status_code;
 mod stream_insert;
+mod trace;
 
+pub use self::bulk_writer::{BulkWriter, BulkWriterOptions, FlushResult, FlushResults};
 pub use self::channel_manager::{ChannelConfig, ChannelManager, ClientTlsOption};
 pub use self::client::{Client, ClientBuilder, Compression};
+pub use self::connection_string::ConnectionString;
 pub use self::database::Database;
 pub use self::error::{Error, Result};
+#[cfg(feature = "flight")]
+pub use self::flight::{Output, RecordBatchStream};
+pub use self::health::{HealthCheckConfig, PeerHealth};
+pub use self::hints::{Hints, MergeMode};
+pub use self::reconnect::{ReconnectOptions, ReconnectingStreamInserter};
+pub use self::request_options::RequestOptions;
+pub use self::retry::RetryPolicy;
+pub use self::spool::{EvictionPolicy, SpoolMetrics, SpoolOptions, SpoolWrite, SpooledWriter};
+pub use self::status_code::StatusCode;
 pub use self::stream_insert::StreamInserter;
 
+#[cfg(feature = "derive")]
+pub use greptimedb_ingester_derive::GreptimeRow;
+
+// The derived code names this crate by its absolute path.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as greptimedb_ingester;
+
 pub const DEFAULT_SCHEMA_NAME: &str = "public";
@@ -12,6 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
 use enum_dispatch::enum_dispatch;
 use rand::seq::SliceRandom;
 
@@ -24,6 +27,7 @@ pub trait LoadBalance {
 #[derive(Debug, Clone)]
 pub enum Loadbalancer {
     Random,
+    RoundRobin,
 }
 
 impl Default for Loadbalancer {
@@ -41,11 +45,28 @@ impl LoadBalance for Random {
     }
 }
 
+/// Picks peers in turn. The cursor is taken modulo the current number of
+/// peers, so it stays valid when the peer list is replaced.
+#[derive(Debug, Clone, Default)]
+pub struct RoundRobin {
+    cursor: Arc<AtomicUsize>,
+}
+
+impl LoadBalance for RoundRobin {
+    fn get_peer<'a>(&self, peers: &'a [String]) -> Option<&'a String> {
+        if peers.is_empty() {
+            return None;
+        }
+        let cursor = self.cursor.fetch_add(1, Ordering::Relaxed);
+        peers.get(cursor % peers.len())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
 
-    use super::{LoadBalance, Random};
+    use super::{LoadBalance, Random, RoundRobin};
 
     #[test]
     fn test_random_lb() {
@@ -63,4 +84,63 @@ mod tests {
             all.contains(peer);
         }
     }
+
+    #[test]
+    fn test_round_robin_lb() {
+        let peers = vec![
+            "127.0.0.1:3001".to_string(),
+            "127.0.0.1:3002".to_string(),
+            "127.0.0.1:3003".to_string(),
+            "127.0.0.1:3004".to_string(),
+        ];
+
+        let round_robin = RoundRobin::default();
+        let mut counts: HashMap<&String, usize> = HashMap::new();
+        for _ in 0..100 {
+            let peer = round_robin.get_peer(&peers).unwrap();
+            *counts.entry(peer).or_default() += 1;
+        }
+        assert_eq!(4, counts.len());
+        assert!(counts.values().all(|count| *count == 25));
+
+        // consecutive picks never repeat a peer
+        let first = round_robin.get_peer(&peers).unwrap();
+        let second = round_robin.get_peer(&peers).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_round_robin_lb_peers_changed() {
+        let round_robin = RoundRobin::default();
+        assert!(round_robin.get_peer(&[]).is_none());
+
+        let peers = vec![
+            "127.0.0.1:3001".to_string(),
+            "127.0.0.1:3002".to_string(),
+            "127.0.0.1:3003".to_string(),
+        ];
+        for _ in 0..5 {
+            round_robin.get_peer(&peers).unwrap();
+        }
+
+        let peers = vec!["127.0.0.1:3004".to_string(), "127.0.0.1:3005".to_string()];
+        let mut counts: HashMap<&String, usize> = HashMap::new();
+        for _ in 0..10 {
+            let peer = round_robin.get_peer(&peers).unwrap();
+            *counts.entry(peer).or_default() += 1;
+        }
+        assert_eq!(2, counts.len());
+        assert!(counts.values().all(|count| *count == 5));
+    }
+
+    #[test]
+    fn test_round_robin_lb_shared_cursor() {
+        let peers = vec!["127.0.0.1:3001".to_string(), "127.0.0.1:3002".to_string()];
+        let round_robin = RoundRobin::default();
+        let cloned = round_robin.clone();
+
+        let first = round_robin.get_peer(&peers).unwrap();
+        let second = cloned.get_peer(&peers).unwrap();
+        assert_ne!(first, second);
+    }
 }
@@ -0,0 +1,211 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Metrics of the client, recorded through the [`metrics`](https://docs.rs/metrics)
+//! facade when the `metrics` feature is enabled.
+//!
+//! Nothing is recorded until the application installs a recorder. To scrape
+//! them with Prometheus, install the `metrics-exporter-prometheus` recorder:
+//!
+//! ```ignore
+//! let handle = PrometheusBuilder::new().install_recorder()?;
+//! // serve `handle.render()` on the metrics endpoint
+//! ```
+//!
+//! Request metrics are labeled by `kind`, the type of the request like
+//! `row_insert`, and errors also by `error`, the [`Error`](crate::Error)
+//! variant like `Server`.
+
+use std::time::Instant;
+
+use crate::api::v1::GreptimeRequest;
+use crate::error::Result;
+
+/// Counter of the requests sent, by `kind`.
+pub const REQUESTS_TOTAL: &str = "greptimedb_ingester_requests_total";
+/// Histogram of the seconds from sending a request to its response,
+/// including retries, by `kind`.
+pub const REQUEST_DURATION_SECONDS: &str = "greptimedb_ingester_request_duration_seconds";
+/// Counter of the rows written or deleted, by `kind`.
+pub const ROWS_TOTAL: &str = "greptimedb_ingester_rows_total";
+/// Counter of the encoded bytes of the requests sent, by `kind`.
+pub const BYTES_SENT_TOTAL: &str = "greptimedb_ingester_bytes_sent_total";
+/// Counter of the failed requests, by `kind` and `error`.
+pub const ERRORS_TOTAL: &str = "greptimedb_ingester_errors_total";
+/// Counter of the retries of failed requests.
+pub const RETRIES_TOTAL: &str = "greptimedb_ingester_retries_total";
+/// Gauge of the requests queued in the channel of the last written
+/// [`StreamInserter`](crate::StreamInserter).
+pub const STREAM_CHANNEL_OCCUPANCY: &str = "greptimedb_ingester_stream_channel_occupancy";
+/// Gauge of the channels in the pool of a [`ChannelManager`](crate::ChannelManager).
+pub const CHANNEL_POOL_SIZE: &str = "greptimedb_ingester_channel_pool_size";
+/// Counter of the idle channels recycled from the pool.
+pub const CHANNEL_RECYCLES_TOTAL: &str = "greptimedb_ingester_channel_recycles_total";
+
+/// Record a request sent at `start` and its result.
+#[cfg(feature = "metrics")]
+pub(crate) fn record_request(request: &GreptimeRequest, start: Instant, result: &Result<u32>) {
+    let kind = record_sent(request);
+    ::metrics::histogram!(REQUEST_DURATION_SECONDS, "kind" => kind)
+        .record(start.elapsed().as_secs_f64());
+    match result {
+        Ok(rows) => ::metrics::counter!(ROWS_TOTAL, "kind" => kind).increment(*rows as u64),
+        Err(e) => record_error(kind, e),
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline]
+pub(crate) fn record_request(_request: &GreptimeRequest, _start: Instant, _result: &Result<u32>) {}
+
+/// Record a request sent on a stream, whose result is recorded by
+/// [`record_stream_finished`].
+#[cfg(feature = "metrics")]
+pub(crate) fn record_stream_request(request: &GreptimeRequest) {
+    record_sent(request);
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline]
+pub(crate) fn record_stream_request(_request: &GreptimeRequest) {}
+
+/// Record the result of a stream opened at `start`.
+#[cfg(feature = "metrics")]
+pub(crate) fn record_stream_finished(start: Instant, result: &Result<u32>) {
+    const KIND: &str = "stream";
+    ::metrics::histogram!(REQUEST_DURATION_SECONDS, "kind" => KIND)
+        .record(start.elapsed().as_secs_f64());
+    match result {
+        Ok(rows) => ::metrics::counter!(ROWS_TOTAL, "kind" => KIND).increment(*rows as u64),
+        Err(e) => record_error(KIND, e),
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline]
+pub(crate) fn record_stream_finished(_start: Instant, _result: &Result<u32>) {}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_retry() {
+    ::metrics::counter!(RETRIES_TOTAL).increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline]
+pub(crate) fn record_retry() {}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_stream_channel_occupancy(occupancy: usize) {
+    ::metrics::gauge!(STREAM_CHANNEL_OCCUPANCY).set(occupancy as f64);
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline]
+pub(crate) fn record_stream_channel_occupancy(_occupancy: usize) {}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_channel_pool(size: usize, recycled: usize) {
+    ::metrics::gauge!(CHANNEL_POOL_SIZE).set(size as f64);
+    ::metrics::counter!(CHANNEL_RECYCLES_TOTAL).increment(recycled as u64);
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline]
+pub(crate) fn record_channel_pool(_size: usize, _recycled: usize) {}
+
+/// The `kind` label of `request`, also used by the spans of the client.
+#[cfg(any(feature = "metrics", feature = "tracing"))]
+pub(crate) fn request_kind(request: &GreptimeRequest) -> &'static str {
+    use crate::api::v1::greptime_request::Request;
+
+    match &request.request {
+        Some(Request::Inserts(_)) => "insert",
+        Some(Request::Query(_)) => "query",
+        Some(Request::Ddl(_)) => "ddl",
+        Some(Request::Deletes(_)) => "delete",
+        Some(Request::RowInserts(_)) => "row_insert",
+        Some(Request::RowDeletes(_)) => "row_delete",
+        None => "unknown",
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn record_sent(request: &GreptimeRequest) -> &'static str {
+    use prost::Message;
+
+    let kind = request_kind(request);
+    ::metrics::counter!(REQUESTS_TOTAL, "kind" => kind).increment(1);
+    ::metrics::counter!(BYTES_SENT_TOTAL, "kind" => kind).increment(request.encoded_len() as u64);
+    kind
+}
+
+#[cfg(feature = "metrics")]
+fn record_error(kind: &'static str, e: &crate::Error) {
+    // The name of the error variant.
+    let error: &'static str = e.into();
+    ::metrics::counter!(ERRORS_TOTAL, "kind" => kind, "error" => error).increment(1);
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use metrics_util::MetricKind;
+
+    use super::*;
+    use crate::api::v1::greptime_request::Request;
+    use crate::api::v1::RowInsertRequests;
+    use crate::error::ClientStreamingSnafu;
+
+    #[test]
+    fn test_record_request() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let request = GreptimeRequest {
+            header: None,
+            request: Some(Request::RowInserts(RowInsertRequests::default())),
+        };
+
+        ::metrics::with_local_recorder(&recorder, || {
+            record_request(&request, Instant::now(), &Ok(3));
+            record_request(
+                &request,
+                Instant::now(),
+                &ClientStreamingSnafu { err_msg: "closed" }.fail(),
+            );
+            record_retry();
+        });
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let counter = |name: &str, label: Option<(&str, &str)>| {
+            snapshot
+                .iter()
+                .find_map(|(key, _, _, value)| {
+                    let key_matches = key.kind() == MetricKind::Counter
+                        && key.key().name() == name
+                        && label.is_none_or(|(k, v)| {
+                            key.key().labels().any(|l| l.key() == k && l.value() == v)
+                        });
+                    match value {
+                        DebugValue::Counter(value) if key_matches => Some(*value),
+                        _ => None,
+                    }
+                })
+                .unwrap_or_default()
+        };
+        assert_eq!(2, counter(REQUESTS_TOTAL, Some(("kind", "row_insert"))));
+        assert_eq!(3, counter(ROWS_TOTAL, Some(("kind", "row_insert"))));
+        assert_eq!(1, counter(ERRORS_TOTAL, Some(("error", "ClientStreaming"))));
+        assert_eq!(1, counter(RETRIES_TOTAL, None));
+    }
+}
@@ -0,0 +1,558 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use snafu::{OptionExt, ResultExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::{Ascii, MetadataValue};
+
+use crate::api::v1::greptime_request::Request;
+use crate::api::v1::{
+    greptime_response, AffectedRows, GreptimeRequest, RowDeleteRequests, RowInsertRequests,
+};
+use crate::client::DatabaseClient;
+use crate::error::{self, IllegalDatabaseResponseSnafu, Result};
+use crate::hints::HINTS_KEY;
+use crate::{Client, Database, Error, Hints, RetryPolicy};
+
+const DEFAULT_REPLAY_BUFFER_SIZE: usize = 1024;
+const DEFAULT_MAX_RECONNECTS: usize = 3;
+const DEFAULT_CHANNEL_SIZE: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReconnectOptions {
+    pub replay_buffer_size: usize,
+    pub max_reconnects: usize,
+    pub channel_size: usize,
+    pub hints: Hints,
+}
+
+impl Default for ReconnectOptions {
+    fn default() -> Self {
+        Self {
+            replay_buffer_size: DEFAULT_REPLAY_BUFFER_SIZE,
+            max_reconnects: DEFAULT_MAX_RECONNECTS,
+            channel_size: DEFAULT_CHANNEL_SIZE,
+            hints: Hints::default(),
+        }
+    }
+}
+
+impl ReconnectOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Requests kept for replay until the server acknowledges them. The
+    /// stream is closed and a new one opened once this many are sent.
+    ///
+    /// Default is 1024
+    pub fn replay_buffer_size(self, replay_buffer_size: usize) -> Self {
+        Self {
+            replay_buffer_size: replay_buffer_size.max(1),
+            ..self
+        }
+    }
+
+    /// Attempts to re-establish a broken stream before giving up, backing off
+    /// between them as the [`RetryPolicy`] of the client.
+    ///
+    /// Default is 3
+    pub fn max_reconnects(self, max_reconnects: usize) -> Self {
+        Self {
+            max_reconnects,
+            ..self
+        }
+    }
+
+    /// Size of the channel between the inserter handle and the background
+    /// task, and of the channel of every stream.
+    ///
+    /// Default is 1024
+    pub fn channel_size(self, channel_size: usize) -> Self {
+        Self {
+            channel_size,
+            ..self
+        }
+    }
+
+    /// Hints sent with every stream.
+    pub fn hints(self, hints: Hints) -> Self {
+        Self { hints, ..self }
+    }
+}
+
+/// A streaming inserter that survives broken streams.
+///
+/// Like [`StreamInserter`](crate::StreamInserter), requests are sent over a
+/// `handle_requests` stream, whose single response acknowledges all of them.
+/// Sent requests are kept in a bounded replay buffer: once it is full the
+/// stream is closed to acknowledge them, and a new one is opened. If the
+/// stream breaks or the server ends it early, it is re-established on another
+/// peer and all buffered requests are replayed.
+///
+/// Replayed requests may have been partially written before the stream broke,
+/// rows with the same primary key and timestamp are then overwritten.
+///
+/// ```ignore
+/// let inserter = database.reconnecting_streaming_inserter(ReconnectOptions::new())?;
+/// inserter.row_insert(requests).await?;
+/// let rows = inserter.finish().await?;
+/// ```
+pub struct ReconnectingStreamInserter {
+    sender: mpsc::Sender<GreptimeRequest>,
+    database: Database,
+    join: JoinHandle<Result<u32>>,
+}
+
+impl ReconnectingStreamInserter {
+    pub(crate) fn new(database: Database, options: ReconnectOptions) -> Result<Self> {
+        let hints = if options.hints.is_empty() {
+            database.hints()
+        } else {
+            &options.hints
+        };
+        let hint = hints.to_metadata_value()?;
+
+        let client = database.client().clone();
+        let policy = client.retry_policy().clone();
+        let channel_size = options.channel_size;
+        let connect =
+            move |exclude: Option<&str>| open_stream(&client, exclude, hint.clone(), channel_size);
+
+        let (sender, receiver) = mpsc::channel(options.channel_size);
+        let replayer = Replayer {
+            connect,
+            policy,
+            buffer_size: options.replay_buffer_size,
+            max_reconnects: options.max_reconnects,
+            buffer: Vec::new(),
+            stream: None,
+            total: 0,
+        };
+        let join = tokio::spawn(replayer.run(receiver));
+
+        Ok(Self {
+            sender,
+            database,
+            join,
+        })
+    }
+
+    /// Write Row based insert requests to GreptimeDB with streaming
+    pub async fn row_insert(&self, requests: RowInsertRequests) -> Result<()> {
+        self.send(Request::RowInserts(requests)).await
+    }
+
+    /// Delete rows from GreptimeDB with streaming, the rows carry the primary
+    /// key and time index values of the rows to delete
+    pub async fn row_delete(&self, requests: RowDeleteRequests) -> Result<()> {
+        self.send(Request::RowDeletes(requests)).await
+    }
+
+    /// Close the stream and get the total rows written by all requests, or
+    /// the error that made the inserter give up.
+    pub async fn finish(self) -> Result<u32> {
+        drop(self.sender);
+
+        self.join.await.context(error::JoinTaskSnafu)?
+    }
+
+    async fn send(&self, request: Request) -> Result<()> {
+        let request = self.database.to_rpc_request(request);
+        self.sender.send(request).await.map_err(|_| {
+            error::ClientStreamingSnafu {
+                err_msg: "Stream inserter gave up, see finish for the error",
+            }
+            .build()
+        })
+    }
+}
+
+/// A single `handle_requests` stream.
+struct Stream {
+    peer: String,
+    sender: mpsc::Sender<GreptimeRequest>,
+    join: JoinHandle<Result<u32>>,
+}
+
+impl Stream {
+    /// Close the stream and wait for the rows written by it.
+    async fn close(self) -> (String, Result<u32>) {
+        drop(self.sender);
+        let result = self
+            .join
+            .await
+            .context(error::JoinTaskSnafu)
+            .and_then(|r| r);
+        (self.peer, result)
+    }
+}
+
+fn open_stream(
+    client: &Client,
+    exclude: Option<&str>,
+    hint: Option<MetadataValue<Ascii>>,
+    channel_size: usize,
+) -> Result<Stream> {
+    let DatabaseClient {
+        peer,
+        inner: mut database_client,
+    } = client.make_database_client_except(exclude)?;
+    let (sender, receiver) = mpsc::channel(channel_size);
+
+    let client = client.clone();
+    let stream_peer = peer.clone();
+    let join = tokio::spawn(async move {
+        let mut request = tonic::Request::new(ReceiverStream::new(receiver));
+        if let Some(hint) = hint {
+            request.metadata_mut().insert(HINTS_KEY, hint);
+        }
+        let response = database_client
+            .handle_requests(request)
+            .await
+            .map_err(Into::into);
+        client.record_peer_result(&stream_peer, &response);
+
+        let response = response?
+            .into_inner()
+            .response
+            .context(IllegalDatabaseResponseSnafu {
+                err_msg: "GreptimeResponse is empty",
+            })?;
+        let greptime_response::Response::AffectedRows(AffectedRows { value }) = response;
+        Ok(value)
+    });
+
+    Ok(Stream { peer, sender, join })
+}
+
+struct Replayer<F> {
+    connect: F,
+    policy: RetryPolicy,
+    buffer_size: usize,
+    max_reconnects: usize,
+    /// Requests sent to the current stream, not acknowledged yet
+    buffer: Vec<GreptimeRequest>,
+    stream: Option<Stream>,
+    total: u32,
+}
+
+impl<F> Replayer<F>
+where
+    F: FnMut(Option<&str>) -> Result<Stream>,
+{
+    async fn run(mut self, mut receiver: mpsc::Receiver<GreptimeRequest>) -> Result<u32> {
+        while let Some(request) = receiver.recv().await {
+            if self.buffer.len() >= self.buffer_size {
+                self.checkpoint().await?;
+            }
+            self.send(request).await?;
+        }
+        self.checkpoint().await?;
+        Ok(self.total)
+    }
+
+    async fn send(&mut self, request: GreptimeRequest) -> Result<()> {
+        self.buffer.push(request.clone());
+        let stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => match (self.connect)(None) {
+                Ok(stream) => stream,
+                // Connecting again replays the buffer, this request included.
+                Err(e) => return self.reconnect(None, e).await,
+            },
+        };
+
+        if stream.sender.send(request).await.is_ok() {
+            self.stream = Some(stream);
+            return Ok(());
+        }
+
+        let (peer, result) = stream.close().await;
+        // The count of rows of a stream the server ended early cannot tell
+        // which of the buffered requests it received, those still queued in
+        // the channel are dropped, so the whole buffer is replayed.
+        let error = result.err().unwrap_or_else(|| {
+            error::ClientStreamingSnafu {
+                err_msg: "Stream ended by the server before all requests were sent",
+            }
+            .build()
+        });
+        self.reconnect(Some(peer), error).await
+    }
+
+    /// Close the current stream, so that the server acknowledges the buffered
+    /// requests, replaying them on another stream if it failed.
+    async fn checkpoint(&mut self) -> Result<()> {
+        while let Some(stream) = self.stream.take() {
+            let (peer, result) = stream.close().await;
+            match result {
+                Ok(rows) => {
+                    self.total = self.total.checked_add(rows).with_context(|| {
+                        IllegalDatabaseResponseSnafu {
+                            err_msg: format!("Affected rows {} + {rows} overflow u32", self.total),
+                        }
+                    })?;
+                    self.buffer.clear();
+                }
+                Err(e) => self.reconnect(Some(peer), e).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Open a new stream on a peer other than `peer`, if known, and replay the
+    /// buffer to it, until it succeeds or the reconnects are exhausted.
+    async fn reconnect(&mut self, mut peer: Option<String>, mut error: Error) -> Result<()> {
+        for attempt in 1..=self.max_reconnects {
+            if !error.is_retriable() {
+                break;
+            }
+            tokio::time::sleep(self.policy.backoff(attempt)).await;
+
+            let stream = match (self.connect)(peer.as_deref()) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error = e;
+                    continue;
+                }
+            };
+            match self.replay(stream).await {
+                Ok(stream) => {
+                    self.stream = Some(stream);
+                    return Ok(());
+                }
+                Err((failed_peer, e)) => {
+                    peer = Some(failed_peer);
+                    error = e;
+                }
+            }
+        }
+        Err(error)
+    }
+
+    /// Send all buffered requests to `stream`, returning the peer and error of
+    /// the stream if it breaks.
+    async fn replay(&self, stream: Stream) -> std::result::Result<Stream, (String, Error)> {
+        for request in &self.buffer {
+            if stream.sender.send(request.clone()).await.is_err() {
+                let (peer, result) = stream.close().await;
+                let error = result.err().unwrap_or_else(|| {
+                    error::ClientStreamingSnafu {
+                        err_msg: "Stream ended during replay",
+                    }
+                    .build()
+                });
+                return Err((peer, error));
+            }
+        }
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tonic::Status;
+
+    use super::*;
+
+    /// A stream that counts the requests it receives, and fails with `status`
+    /// after receiving `fail_after` of them.
+    fn mock_stream(peer: &str, fail_after: Option<(usize, Status)>) -> Stream {
+        let (sender, mut receiver) = mpsc::channel(1);
+        let join = tokio::spawn(async move {
+            let mut received = 0;
+            while receiver.recv().await.is_some() {
+                received += 1;
+                if let Some((fail_after, status)) = &fail_after {
+                    if received == *fail_after {
+                        return Err(status.clone().into());
+                    }
+                }
+            }
+            Ok(received as u32)
+        });
+        Stream {
+            peer: peer.to_string(),
+            sender,
+            join,
+        }
+    }
+
+    /// A stream with room for `capacity` queued requests, that the server
+    /// ends successfully after receiving `end_after` of them.
+    fn ending_stream(peer: &str, capacity: usize, end_after: usize) -> Stream {
+        let (sender, mut receiver) = mpsc::channel(capacity);
+        let join = tokio::spawn(async move {
+            let mut received = 0;
+            while received < end_after && receiver.recv().await.is_some() {
+                received += 1;
+            }
+            Ok(received as u32)
+        });
+        Stream {
+            peer: peer.to_string(),
+            sender,
+            join,
+        }
+    }
+
+    fn replayer<F>(connect: F, buffer_size: usize) -> Replayer<F> {
+        Replayer {
+            connect,
+            policy: RetryPolicy::new().base_backoff(Duration::from_millis(1)),
+            buffer_size,
+            max_reconnects: 2,
+            buffer: Vec::new(),
+            stream: None,
+            total: 0,
+        }
+    }
+
+    async fn send_requests<F>(replayer: Replayer<F>, n: usize) -> Result<u32>
+    where
+        F: FnMut(Option<&str>) -> Result<Stream>,
+    {
+        let (sender, receiver) = mpsc::channel(n);
+        for _ in 0..n {
+            sender.send(GreptimeRequest::default()).await.unwrap();
+        }
+        drop(sender);
+        replayer.run(receiver).await
+    }
+
+    #[tokio::test]
+    async fn test_replay_after_failure() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let counter = connects.clone();
+        let connect = move |exclude: Option<&str>| {
+            let n = counter.fetch_add(1, Ordering::Relaxed);
+            if n == 0 {
+                Ok(mock_stream("a", Some((2, Status::unavailable("down")))))
+            } else {
+                if n == 1 {
+                    // the reconnect avoids the failed peer
+                    assert_eq!(Some("a"), exclude);
+                }
+                Ok(mock_stream("b", None))
+            }
+        };
+
+        let total = send_requests(replayer(connect, 3), 5).await.unwrap();
+        // every request is acknowledged exactly once
+        assert_eq!(5, total);
+        assert!(connects.load(Ordering::Relaxed) >= 2);
+    }
+
+    #[tokio::test]
+    async fn test_replay_after_early_end() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let counter = connects.clone();
+        let connect = move |exclude: Option<&str>| {
+            if counter.fetch_add(1, Ordering::Relaxed) == 0 {
+                // requests 2 and 3 are still queued when the server ends the
+                // stream, and sending request 4 fails
+                Ok(ending_stream("a", 2, 1))
+            } else {
+                assert_eq!(Some("a"), exclude);
+                Ok(mock_stream("b", None))
+            }
+        };
+
+        // the whole buffer is replayed to the new stream, which acknowledges
+        // every request
+        let total = send_requests(replayer(connect, 10), 5).await.unwrap();
+        assert_eq!(5, total);
+        assert_eq!(2, connects.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_retry_first_connect() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let counter = connects.clone();
+        let connect = move |exclude: Option<&str>| {
+            assert_eq!(None, exclude);
+            if counter.fetch_add(1, Ordering::Relaxed) == 0 {
+                Err(Status::unavailable("down").into())
+            } else {
+                Ok(mock_stream("a", None))
+            }
+        };
+
+        let total = send_requests(replayer(connect, 10), 5).await.unwrap();
+        assert_eq!(5, total);
+        assert_eq!(2, connects.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_total_overflow() {
+        // every stream acknowledges more rows than fit in u32 once summed
+        let connect = |_: Option<&str>| {
+            let (sender, mut receiver) = mpsc::channel(1);
+            let join = tokio::spawn(async move {
+                while receiver.recv().await.is_some() {}
+                Ok(u32::MAX)
+            });
+            Ok(Stream {
+                peer: "a".to_string(),
+                sender,
+                join,
+            })
+        };
+
+        let err = send_requests(replayer(connect, 1), 2).await.unwrap_err();
+        assert!(matches!(err, Error::IllegalDatabaseResponse { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_give_up() {
+        let connect =
+            |_: Option<&str>| Ok(mock_stream("a", Some((1, Status::unavailable("down")))));
+        let err = send_requests(replayer(connect, 3), 5).await.unwrap_err();
+        assert!(matches!(err, Error::Server { .. }));
+
+        // the connect error is returned if no peer is available
+        let connect = |_: Option<&str>| {
+            error::IllegalGrpcClientStateSnafu {
+                err_msg: "No available peer found",
+            }
+            .fail()
+        };
+        let err = send_requests(replayer(connect, 3), 1).await.unwrap_err();
+        assert!(matches!(err, Error::IllegalGrpcClientState { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_no_reconnect_on_invalid_request() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let counter = connects.clone();
+        let connect = move |_: Option<&str>| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(mock_stream(
+                "a",
+                Some((1, Status::invalid_argument("bad request"))),
+            ))
+        };
+
+        let err = send_requests(replayer(connect, 3), 2).await.unwrap_err();
+        assert!(!err.is_retriable());
+        assert_eq!(1, connects.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use tonic::metadata::{Ascii, MetadataKey, MetadataMap, MetadataValue};
+
+use crate::error::{InvalidAsciiSnafu, InvalidMetadataKeySnafu, Result};
+use crate::hints::HINTS_KEY;
+use crate::Hints;
+
+/// Options of a single request, overriding those of the [`Database`](crate::Database).
+///
+/// ```ignore
+/// let options = RequestOptions::new()
+///     .timeout(Duration::from_secs(3))
+///     .dbname("metrics")
+///     .metadata("x-request-id", "42")
+///     .hints(Hints::new().append_mode(true));
+/// database.row_insert_with_options(requests, &options).await?;
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestOptions {
+    pub timeout: Option<Duration>,
+    pub metadata: Vec<(String, String)>,
+    pub dbname: Option<String>,
+    pub hints: Hints,
+}
+
+impl RequestOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Deadline of the request, sent to the server as `grpc-timeout`. It
+    /// covers all retries of the request, all the requests a large row
+    /// insert is split into, and the whole stream of a streaming inserter.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Add a gRPC metadata header. Keys are lowercase ASCII, values visible
+    /// ASCII.
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.push((key.into(), value.into()));
+        self
+    }
+
+    /// Write into this database instead of the one of the [`Database`](crate::Database).
+    pub fn dbname(self, dbname: impl Into<String>) -> Self {
+        Self {
+            dbname: Some(dbname.into()),
+            ..self
+        }
+    }
+
+    /// Hints of the request, replacing an `x-greptime-hints` header added by
+    /// [`metadata`](Self::metadata).
+    pub fn hints(self, hints: Hints) -> Self {
+        Self { hints, ..self }
+    }
+
+    /// The validated metadata of the request.
+    pub(crate) fn to_metadata(&self) -> Result<MetadataMap> {
+        let mut metadata = MetadataMap::new();
+        for (key, value) in &self.metadata {
+            let key = MetadataKey::<Ascii>::from_bytes(key.as_bytes())
+                .map_err(|_| InvalidMetadataKeySnafu { key }.build())?;
+            let value = MetadataValue::try_from(value.as_str())
+                .map_err(|_| InvalidAsciiSnafu { value }.build())?;
+            metadata.append(key, value);
+        }
+        if let Some(hints) = self.hints.to_metadata_value()? {
+            metadata.insert(HINTS_KEY, hints);
+        }
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn test_to_metadata() {
+        let metadata = RequestOptions::new()
+            .metadata("x-request-id", "1")
+            .metadata("x-request-id", "2")
+            .metadata(HINTS_KEY, "ttl=1d")
+            .hints(Hints::new().append_mode(true))
+            .to_metadata()
+            .unwrap();
+        let ids: Vec<_> = metadata
+            .get_all("x-request-id")
+            .iter()
+            .map(|v| v.to_str().unwrap())
+            .collect();
+        assert_eq!(vec!["1", "2"], ids);
+        assert_eq!("append_mode=true", metadata.get(HINTS_KEY).unwrap());
+
+        assert!(matches!(
+            RequestOptions::new()
+                .metadata("x request id", "1")
+                .to_metadata()
+                .unwrap_err(),
+            Error::InvalidMetadataKey { .. }
+        ));
+        assert!(matches!(
+            RequestOptions::new()
+                .metadata("x-request-id", "\n")
+                .to_metadata()
+                .unwrap_err(),
+            Error::InvalidAscii { .. }
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+use tokio::time::Instant;
+
+use crate::error::RetryDeadlineExceededSnafu;
+use crate::Result;
+
+/// Policy to retry requests failed with a retriable error, see
+/// [`Error::is_retriable`](crate::Error::is_retriable).
+///
+/// The default policy makes a single attempt, i.e. never retries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    pub jitter: bool,
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            base_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Maximum number of attempts, including the first one.
+    ///
+    /// Default is 1
+    pub fn max_attempts(self, max_attempts: usize) -> Self {
+        Self {
+            max_attempts,
+            ..self
+        }
+    }
+
+    /// Backoff before the first retry, doubled on every following retry.
+    ///
+    /// Default is 100ms
+    pub fn base_backoff(self, base_backoff: Duration) -> Self {
+        Self {
+            base_backoff,
+            ..self
+        }
+    }
+
+    /// Upper bound of the backoff between two attempts.
+    ///
+    /// Default is 10s
+    pub fn max_backoff(self, max_backoff: Duration) -> Self {
+        Self {
+            max_backoff,
+            ..self
+        }
+    }
+
+    /// Whether to randomize each backoff between half and all of its value.
+    ///
+    /// Enabled by default.
+    pub fn jitter(self, jitter: bool) -> Self {
+        Self { jitter, ..self }
+    }
+
+    /// Total time budget of all attempts. An attempt still running at the
+    /// deadline is cancelled, and no retry is started if its backoff would
+    /// end after the deadline.
+    ///
+    /// Default is no deadline (None)
+    pub fn deadline(self, deadline: Duration) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..self
+        }
+    }
+
+    /// Backoff to wait after the given attempt failed, starting from 1.
+    pub(crate) fn backoff(&self, attempt: usize) -> Duration {
+        let exp = attempt.saturating_sub(1).min(31) as u32;
+        let backoff = self
+            .base_backoff
+            .saturating_mul(1 << exp)
+            .min(self.max_backoff);
+
+        if self.jitter && !backoff.is_zero() {
+            let half = backoff / 2;
+            half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+        } else {
+            backoff
+        }
+    }
+
+    /// Run `f` until it succeeds, fails with a non-retriable error, or the
+    /// attempts or deadline are exhausted. The last error is returned.
+    pub(crate) async fn retry<T, F, Fut>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let start = Instant::now();
+        let mut attempt = 1;
+
+        loop {
+            let result = match self.deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_sub(start.elapsed());
+                    tokio::time::timeout(remaining, f())
+                        .await
+                        .unwrap_or_else(|_| RetryDeadlineExceededSnafu { deadline }.fail())
+                }
+                None => f().await,
+            };
+            let err = match result {
+                Ok(v) => return Ok(v),
+                Err(e) => e,
+            };
+            if !err.is_retriable() || attempt >= self.max_attempts {
+                return Err(err);
+            }
+
+            let backoff = self.backoff(attempt);
+            if let Some(deadline) = self.deadline {
+                if start.elapsed() + backoff >= deadline {
+                    return Err(err);
+                }
+            }
+
+            tokio::time::sleep(backoff).await;
+            crate::metrics::record_retry();
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tonic::Status;
+
+    use super::*;
+    use crate::error::MissingFieldSnafu;
+    use crate::Error;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new()
+            .base_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500))
+            .jitter(false);
+
+        assert_eq!(Duration::from_millis(100), policy.backoff(1));
+        assert_eq!(Duration::from_millis(200), policy.backoff(2));
+        assert_eq!(Duration::from_millis(400), policy.backoff(3));
+        assert_eq!(Duration::from_millis(500), policy.backoff(4));
+        assert_eq!(Duration::from_millis(500), policy.backoff(100));
+
+        let policy = policy.jitter(true);
+        for attempt in 1..10 {
+            let backoff = policy.backoff(attempt);
+            let max = policy.clone().jitter(false).backoff(attempt);
+            assert!(backoff >= max / 2 && backoff <= max);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let policy = RetryPolicy::new()
+            .max_attempts(3)
+            .base_backoff(Duration::from_millis(1));
+
+        let attempts = AtomicUsize::new(0);
+        let result = policy
+            .retry(|| async {
+                if attempts.fetch_add(1, Ordering::Relaxed) < 2 {
+                    Err(Status::unavailable("retry").into())
+                } else {
+                    Ok(1)
+                }
+            })
+            .await;
+        assert_eq!(1, result.unwrap());
+        assert_eq!(3, attempts.load(Ordering::Relaxed));
+
+        let attempts = AtomicUsize::new(0);
+        let result: Result<()> = policy
+            .retry(|| async {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                Err(Status::unavailable("retry").into())
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(3, attempts.load(Ordering::Relaxed));
+
+        let attempts = AtomicUsize::new(0);
+        let result: Result<()> = policy
+            .retry(|| async {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                MissingFieldSnafu { field: "header" }.fail()
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(1, attempts.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_retry_deadline() {
+        let policy = RetryPolicy::new()
+            .max_attempts(10)
+            .base_backoff(Duration::from_secs(1))
+            .deadline(Duration::from_millis(500));
+
+        let attempts = AtomicUsize::new(0);
+        let result: Result<()> = policy
+            .retry(|| async {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                Err(Status::unavailable("retry").into())
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(1, attempts.load(Ordering::Relaxed));
+
+        // A slow attempt is cancelled at the deadline.
+        let start = Instant::now();
+        let result: Result<()> = policy
+            .retry(|| async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(())
+            })
+            .await;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let err = result.unwrap_err();
+        assert!(
+            matches!(err, Error::RetryDeadlineExceeded { .. }),
+            "{err:?}"
+        );
+        assert!(!err.is_retriable());
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::mem;
+
+use prost::{encoding, Message};
+use snafu::ensure;
+
+use crate::api::v1::{RowInsertRequest, RowInsertRequests, Rows};
+use crate::error::{RequestTooLargeSnafu, Result};
+
+/// Split `requests` into chunks whose encoded size is at most `max_size`.
+///
+/// Tables are kept in one chunk when they fit, otherwise their rows are
+/// spread over consecutive chunks in order, each with a copy of the schema.
+pub(crate) fn split_row_inserts(
+    requests: RowInsertRequests,
+    max_size: usize,
+) -> Result<Vec<RowInsertRequests>> {
+    if requests.encoded_len() <= max_size {
+        return Ok(vec![requests]);
+    }
+
+    let mut splitter = Splitter {
+        max_size,
+        chunks: vec![],
+        chunk: RowInsertRequests::default(),
+        size: 0,
+    };
+    for insert in requests.inserts {
+        let len = field_len(insert.encoded_len());
+        if splitter.size + len > max_size {
+            if len <= max_size {
+                splitter.flush();
+            } else {
+                splitter.split(insert)?;
+                continue;
+            }
+        }
+        splitter.push(insert, len);
+    }
+    splitter.flush();
+    Ok(splitter.chunks)
+}
+
+struct Splitter {
+    max_size: usize,
+    chunks: Vec<RowInsertRequests>,
+    chunk: RowInsertRequests,
+    // Encoded size of `chunk`.
+    size: usize,
+}
+
+impl Splitter {
+    fn push(&mut self, insert: RowInsertRequest, len: usize) {
+        self.chunk.inserts.push(insert);
+        self.size += len;
+    }
+
+    fn flush(&mut self) {
+        if !self.chunk.inserts.is_empty() {
+            self.chunks.push(mem::take(&mut self.chunk));
+        }
+        self.size = 0;
+    }
+
+    /// Spread the rows of a table larger than a chunk over several chunks.
+    fn split(&mut self, mut insert: RowInsertRequest) -> Result<()> {
+        let Rows { schema, rows } = insert.rows.take().unwrap_or_default();
+        let table_len = insert.encoded_len();
+        let empty_rows = Rows {
+            schema,
+            rows: vec![],
+        };
+        let empty_rows_len = empty_rows.encoded_len();
+        // Encoded size of the insert with rows of `rows_len` bytes.
+        let insert_len = |rows_len| field_len(table_len + field_len(rows_len));
+
+        let mut piece = RowInsertRequest {
+            table_name: insert.table_name,
+            rows: Some(empty_rows.clone()),
+        };
+        let mut rows_len = empty_rows_len;
+        for row in rows {
+            let row_len = field_len(row.encoded_len());
+            if self.size + insert_len(rows_len + row_len) > self.max_size {
+                let has_rows = rows_len > empty_rows_len;
+                if has_rows {
+                    let next = RowInsertRequest {
+                        table_name: piece.table_name.clone(),
+                        rows: Some(empty_rows.clone()),
+                    };
+                    self.push(mem::replace(&mut piece, next), insert_len(rows_len));
+                    rows_len = empty_rows_len;
+                }
+                self.flush();
+                ensure!(
+                    insert_len(rows_len + row_len) <= self.max_size,
+                    RequestTooLargeSnafu {
+                        table: &piece.table_name,
+                        size: insert_len(rows_len + row_len),
+                        max_size: self.max_size,
+                    }
+                );
+            }
+            piece.rows.as_mut().unwrap().rows.push(row);
+            rows_len += row_len;
+        }
+        if rows_len > empty_rows_len {
+            self.push(piece, insert_len(rows_len));
+        }
+        Ok(())
+    }
+}
+
+/// Encoded size of a message field of `len` bytes, with its key and length.
+fn field_len(len: usize) -> usize {
+    encoding::key_len(1) + encoding::encoded_len_varint(len as u64) + len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::v1::{ColumnDataType, Row};
+    use crate::helpers::schema::{field, timestamp};
+    use crate::helpers::values::{string_value, timestamp_millisecond_value};
+    use crate::Error;
+
+    fn insert(table_name: &str, rows: usize, value_len: usize) -> RowInsertRequest {
+        RowInsertRequest {
+            table_name: table_name.to_string(),
+            rows: Some(Rows {
+                schema: vec![
+                    timestamp("ts", ColumnDataType::TimestampMillisecond),
+                    field("message", ColumnDataType::String),
+                ],
+                rows: (0..rows)
+                    .map(|i| Row {
+                        values: vec![
+                            timestamp_millisecond_value(i as i64),
+                            string_value("x".repeat(value_len)),
+                        ],
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    fn rows(chunks: &[RowInsertRequests], table: &str) -> Vec<i64> {
+        use crate::api::v1::value::ValueData;
+
+        chunks
+            .iter()
+            .flat_map(|chunk| &chunk.inserts)
+            .filter(|insert| insert.table_name == table)
+            .flat_map(|insert| &insert.rows.as_ref().unwrap().rows)
+            .map(|row| match row.values[0].value_data {
+                Some(ValueData::TimestampMillisecondValue(ts)) => ts,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_row_inserts() {
+        let requests = RowInsertRequests {
+            inserts: vec![
+                insert("a", 3, 100),
+                insert("b", 50, 100),
+                insert("c", 2, 100),
+            ],
+        };
+        let max_size = 1024;
+        assert_eq!(
+            vec![requests.clone()],
+            split_row_inserts(requests.clone(), requests.encoded_len()).unwrap()
+        );
+
+        let chunks = split_row_inserts(requests, max_size).unwrap();
+        assert!(chunks.len() > 5);
+        for chunk in &chunks {
+            assert!(chunk.encoded_len() <= max_size, "{}", chunk.encoded_len());
+        }
+        assert_eq!(vec![0, 1, 2], rows(&chunks, "a"));
+        assert_eq!((0..50).collect::<Vec<_>>(), rows(&chunks, "b"));
+        assert_eq!(vec![0, 1], rows(&chunks, "c"));
+        // The small tables are not split.
+        assert_eq!(
+            1,
+            chunks
+                .iter()
+                .filter(|c| c.inserts[0].table_name == "a")
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_row_too_large() {
+        let requests = RowInsertRequests {
+            inserts: vec![insert("a", 2, 2000)],
+        };
+        let err = split_row_inserts(requests, 1024).unwrap_err();
+        assert!(matches!(err, Error::RequestTooLarge { max_size: 1024, .. }));
+    }
+}
@@ -0,0 +1,784 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::warn;
+use parking_lot::Mutex;
+use prost::Message;
+use snafu::{ensure, ResultExt};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::api::v1::RowInsertRequests;
+use crate::error::{self, Result};
+use crate::{Database, RequestOptions};
+
+const DEFAULT_SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+const DEFAULT_MAX_SIZE: u64 = 1024 * 1024 * 1024;
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+const SEGMENT_SUFFIX: &str = ".log";
+const CHECKPOINT_FILE: &str = "checkpoint";
+// Every record is its little endian u32 length followed by the encoded
+// `RowInsertRequests`.
+const HEADER_LEN: u64 = 4;
+
+/// What to do when a request does not fit in the spool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Delete the oldest segments, losing the requests in them.
+    #[default]
+    DropOldest,
+    /// Fail the write with [`Error::SpoolFull`](crate::Error::SpoolFull).
+    RejectNew,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpoolOptions {
+    pub dir: PathBuf,
+    pub segment_size: u64,
+    pub max_size: u64,
+    pub eviction: EvictionPolicy,
+    pub sync: bool,
+    pub check_interval: Duration,
+}
+
+impl SpoolOptions {
+    /// Spool into `dir`, which is created if missing. A directory must not be
+    /// used by more than one [`SpooledWriter`] at a time.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            max_size: DEFAULT_MAX_SIZE,
+            eviction: EvictionPolicy::default(),
+            sync: false,
+            check_interval: DEFAULT_CHECK_INTERVAL,
+        }
+    }
+
+    /// A new segment file is started once the current one reaches this size.
+    ///
+    /// Default is 16MiB
+    pub fn segment_size(self, segment_size: u64) -> Self {
+        Self {
+            segment_size,
+            ..self
+        }
+    }
+
+    /// Total size of the segment files, the [`EvictionPolicy`] applies beyond
+    /// it.
+    ///
+    /// Default is 1GiB
+    pub fn max_size(self, max_size: u64) -> Self {
+        Self { max_size, ..self }
+    }
+
+    /// Default is [`EvictionPolicy::DropOldest`]
+    pub fn eviction(self, eviction: EvictionPolicy) -> Self {
+        Self { eviction, ..self }
+    }
+
+    /// Sync every spooled request to disk before the write returns.
+    ///
+    /// Default is false
+    pub fn sync(self, sync: bool) -> Self {
+        Self { sync, ..self }
+    }
+
+    /// Interval between health checks while there are requests to replay.
+    ///
+    /// Default is 5s
+    pub fn check_interval(self, check_interval: Duration) -> Self {
+        Self {
+            check_interval,
+            ..self
+        }
+    }
+}
+
+/// Counters of a [`SpooledWriter`] since it was created.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpoolMetrics {
+    /// Requests appended to the spool
+    pub spooled_requests: u64,
+    /// Requests not spooled because the spool was full
+    pub rejected_requests: u64,
+    /// Bytes of requests deleted before being replayed
+    pub evicted_bytes: u64,
+    /// Requests replayed successfully
+    pub replayed_requests: u64,
+    /// Rows written by the replayed requests
+    pub replayed_rows: u64,
+    /// Requests dropped because the server rejected them on replay
+    pub dropped_requests: u64,
+    /// Bytes of requests waiting to be replayed
+    pub pending_bytes: u64,
+}
+
+/// Outcome of [`SpooledWriter::row_insert`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpoolWrite {
+    /// Written to the database, with the rows written
+    Written(u32),
+    /// Appended to the spool, to be replayed later
+    Spooled,
+}
+
+/// A writer that spools requests to disk while GreptimeDB is unreachable.
+///
+/// Requests failing with a retriable error are appended to a segmented log
+/// in the spool directory. A background task replays them in order once
+/// [`Client::health_check`](crate::Client::health_check) succeeds. While
+/// requests are waiting for replay, new requests are spooled after them
+/// instead of being sent, to keep the order of writes. For the same reason
+/// requests are written one at a time.
+///
+/// Requests left in the spool directory are replayed by the next writer
+/// opened on it. Replay is at least once: a request written just before a
+/// crash may be replayed again, overwriting the same rows.
+///
+/// ```ignore
+/// let writer = database.spooled_writer(SpoolOptions::new("/var/lib/collector/spool"))?;
+/// match writer.row_insert(requests).await? {
+///     SpoolWrite::Written(rows) => println!("{rows} rows written"),
+///     SpoolWrite::Spooled => println!("spooled for later"),
+/// }
+/// ```
+pub struct SpooledWriter {
+    database: Database,
+    // Held from the check of the spool to the write or spooling of a request,
+    // so no request overtakes one being spooled.
+    order: tokio::sync::Mutex<()>,
+    log: Arc<Mutex<SpoolLog>>,
+    notify: Arc<Notify>,
+    join: JoinHandle<()>,
+}
+
+impl SpooledWriter {
+    pub(crate) fn new(database: Database, options: SpoolOptions) -> Result<Self> {
+        let log = Arc::new(Mutex::new(SpoolLog::open(&options)?));
+        let notify = Arc::new(Notify::new());
+        // Replay what a previous writer left behind.
+        notify.notify_one();
+
+        let join = tokio::spawn(replay(
+            database.clone(),
+            log.clone(),
+            notify.clone(),
+            options.check_interval,
+        ));
+
+        Ok(Self {
+            database,
+            order: tokio::sync::Mutex::new(()),
+            log,
+            notify,
+            join,
+        })
+    }
+
+    /// Write `requests`, or spool them if the database is unreachable. Other
+    /// errors are returned as is.
+    ///
+    /// Requests larger than the maximum request size are written in several
+    /// requests, as with [`Database::row_insert`]. If the database becomes
+    /// unreachable in between, only the requests not yet written are spooled,
+    /// and [`SpoolWrite::Spooled`] is returned although some rows were written.
+    pub async fn row_insert(&self, requests: RowInsertRequests) -> Result<SpoolWrite> {
+        let mut chunks = self
+            .database
+            .split_row_inserts(requests, &RequestOptions::default())?
+            .into_iter();
+        let count = chunks.len();
+        let _order = self.order.lock().await;
+        // The log stays locked during file I/O, which must not block the runtime.
+        let mut pending = Vec::new();
+        if with_log(&self.log, |log| Ok(log.is_empty())).await? {
+            let mut affected_rows = 0;
+            for (index, chunk) in chunks.by_ref().enumerate() {
+                match self.database.row_insert(chunk.clone()).await {
+                    Ok(rows) => affected_rows += rows,
+                    Err(e) if e.is_transient() => {
+                        pending.push(chunk);
+                        break;
+                    }
+                    Err(e) if index == 0 => return Err(e),
+                    Err(e) => {
+                        return Err(e).context(error::PartialRowInsertSnafu {
+                            chunk: index,
+                            chunks: count,
+                            affected_rows,
+                        })
+                    }
+                }
+            }
+            if pending.is_empty() {
+                return Ok(SpoolWrite::Written(affected_rows));
+            }
+        }
+
+        pending.extend(chunks);
+        let data: Vec<_> = pending.iter().map(Message::encode_to_vec).collect();
+        with_log(&self.log, move |log| {
+            data.iter().try_for_each(|data| log.append(data))
+        })
+        .await?;
+        self.notify.notify_one();
+        Ok(SpoolWrite::Spooled)
+    }
+
+    pub fn metrics(&self) -> SpoolMetrics {
+        self.log.lock().metrics()
+    }
+}
+
+impl Drop for SpooledWriter {
+    fn drop(&mut self) {
+        // Requests not replayed yet stay in the spool directory.
+        self.join.abort();
+    }
+}
+
+async fn replay(
+    database: Database,
+    log: Arc<Mutex<SpoolLog>>,
+    notify: Arc<Notify>,
+    check_interval: Duration,
+) {
+    loop {
+        if let Ok(true) = with_log(&log, |log| Ok(log.is_empty())).await {
+            notify.notified().await;
+            continue;
+        }
+
+        if database.client().health_check().await.is_ok() && replay_pending(&database, &log).await {
+            continue;
+        }
+        tokio::time::sleep(check_interval).await;
+    }
+}
+
+/// Replay spooled requests until the spool is empty, which returns true, or
+/// until a request fails with a retriable error.
+async fn replay_pending(database: &Database, log: &Arc<Mutex<SpoolLog>>) -> bool {
+    loop {
+        let next = with_log(log, SpoolLog::peek).await;
+        let (requests, next) = match next {
+            Ok(Some(record)) => record,
+            Ok(None) => return true,
+            Err(e) => {
+                warn!("Failed to read spooled requests: {e}");
+                return false;
+            }
+        };
+
+        let result = database.row_insert(requests).await;
+        if matches!(&result, Err(e) if e.is_transient()) {
+            return false;
+        }
+        let committed = with_log(log, move |spool| {
+            match result {
+                Ok(rows) => {
+                    spool.metrics.replayed_requests += 1;
+                    spool.metrics.replayed_rows += rows as u64;
+                }
+                Err(e) => {
+                    warn!("Dropped spooled requests that failed: {e}");
+                    spool.metrics.dropped_requests += 1;
+                }
+            }
+            spool.advance(next)
+        })
+        .await;
+        if let Err(e) = committed {
+            warn!("Failed to save spool checkpoint: {e}");
+        }
+    }
+}
+
+/// Run `f` on the log in a blocking thread, it reads and writes files.
+async fn with_log<T, F>(log: &Arc<Mutex<SpoolLog>>, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut SpoolLog) -> Result<T> + Send + 'static,
+{
+    let log = log.clone();
+    tokio::task::spawn_blocking(move || f(&mut log.lock()))
+        .await
+        .context(error::JoinTaskSnafu)?
+}
+
+#[derive(Debug)]
+struct Segment {
+    id: u64,
+    size: u64,
+}
+
+/// Position of the next record to replay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cursor {
+    segment: u64,
+    offset: u64,
+}
+
+/// Segment files of the spool, named after their increasing ids. The last
+/// segment is appended to, segments before the cursor are deleted.
+struct SpoolLog {
+    dir: PathBuf,
+    segment_size: u64,
+    max_size: u64,
+    eviction: EvictionPolicy,
+    sync: bool,
+    segments: VecDeque<Segment>,
+    writer: File,
+    cursor: Cursor,
+    metrics: SpoolMetrics,
+}
+
+impl SpoolLog {
+    fn open(options: &SpoolOptions) -> Result<Self> {
+        let dir = options.dir.clone();
+        fs::create_dir_all(&dir).context(error::SpoolIoSnafu)?;
+
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&dir).context(error::SpoolIoSnafu)? {
+            let entry = entry.context(error::SpoolIoSnafu)?;
+            let name = entry.file_name();
+            let Some(id) = name
+                .to_str()
+                .and_then(|name| name.strip_suffix(SEGMENT_SUFFIX))
+                .and_then(|id| id.parse().ok())
+            else {
+                continue;
+            };
+            let size = entry.metadata().context(error::SpoolIoSnafu)?.len();
+            segments.push(Segment { id, size });
+        }
+        segments.sort_by_key(|segment| segment.id);
+
+        let cursor = read_checkpoint(&dir).context(error::SpoolIoSnafu)?;
+        let mut segments = VecDeque::from(segments);
+        while let Some(segment) = segments.front() {
+            if !cursor.is_some_and(|cursor| segment.id < cursor.segment) {
+                break;
+            }
+            fs::remove_file(segment_path(&dir, segment.id)).context(error::SpoolIoSnafu)?;
+            segments.pop_front();
+        }
+
+        // Never append to a segment of a previous writer, it may end with a
+        // torn record.
+        let id = segments.back().map_or(0, |segment| segment.id + 1);
+        let writer = create_segment(&dir, id).context(error::SpoolIoSnafu)?;
+        segments.push_back(Segment { id, size: 0 });
+
+        let first = segments[0].id;
+        let cursor = match cursor {
+            Some(cursor) if cursor.segment == first => cursor,
+            _ => Cursor {
+                segment: first,
+                offset: 0,
+            },
+        };
+
+        Ok(Self {
+            dir,
+            segment_size: options.segment_size,
+            max_size: options.max_size,
+            eviction: options.eviction,
+            sync: options.sync,
+            segments,
+            writer,
+            cursor,
+            metrics: SpoolMetrics::default(),
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending_bytes() == 0
+    }
+
+    fn pending_bytes(&self) -> u64 {
+        self.segments
+            .iter()
+            .map(|segment| segment.size)
+            .sum::<u64>()
+            - self.cursor.offset
+    }
+
+    fn metrics(&self) -> SpoolMetrics {
+        SpoolMetrics {
+            pending_bytes: self.pending_bytes(),
+            ..self.metrics.clone()
+        }
+    }
+
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        let len = HEADER_LEN + data.len() as u64;
+        if let Err(e) = self.make_room(len) {
+            self.metrics.rejected_requests += 1;
+            return Err(e);
+        }
+
+        let mut record = Vec::with_capacity(len as usize);
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(data);
+        if let Err(e) = self.write(&record) {
+            // Cut off what was written of the record, or failing that start
+            // a new segment, so the next records are not read after torn
+            // bytes.
+            let size = self.active_mut().size;
+            if self.writer.set_len(size).is_err() {
+                self.rotate()?;
+            }
+            return Err(e).context(error::SpoolIoSnafu);
+        }
+
+        self.active_mut().size += len;
+        self.metrics.spooled_requests += 1;
+        Ok(())
+    }
+
+    fn write(&mut self, record: &[u8]) -> io::Result<()> {
+        self.writer.write_all(record)?;
+        if self.sync {
+            self.writer.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn make_room(&mut self, len: u64) -> Result<()> {
+        ensure!(len <= self.max_size, error::SpoolFullSnafu);
+
+        let active = self.active_mut();
+        if active.size > 0 && active.size + len > self.segment_size {
+            self.rotate()?;
+        }
+
+        while self.size() + len > self.max_size {
+            ensure!(
+                self.eviction == EvictionPolicy::DropOldest,
+                error::SpoolFullSnafu
+            );
+            if self.segments.len() == 1 {
+                self.rotate()?;
+            }
+            self.evict_oldest()?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let id = self.active_mut().id + 1;
+        self.writer = create_segment(&self.dir, id).context(error::SpoolIoSnafu)?;
+        self.segments.push_back(Segment { id, size: 0 });
+        Ok(())
+    }
+
+    fn evict_oldest(&mut self) -> Result<()> {
+        let segment = self.pop_oldest()?;
+        self.metrics.evicted_bytes += segment.size - self.cursor.offset;
+        self.cursor = Cursor {
+            segment: self.segments[0].id,
+            offset: 0,
+        };
+        Ok(())
+    }
+
+    /// Read the record at the cursor, and the cursor after it.
+    fn peek(&mut self) -> Result<Option<(RowInsertRequests, Cursor)>> {
+        loop {
+            let segment = &self.segments[0];
+            if let Some((data, offset)) = self.read_record(segment).context(error::SpoolIoSnafu)? {
+                let next = Cursor {
+                    segment: segment.id,
+                    offset,
+                };
+                match RowInsertRequests::decode(data.as_slice()) {
+                    Ok(requests) => return Ok(Some((requests, next))),
+                    Err(e) => warn!(
+                        "Skipped corrupted spool segment {}: {e}",
+                        segment_path(&self.dir, segment.id).display()
+                    ),
+                }
+            }
+
+            if self.segments.len() == 1 {
+                return Ok(None);
+            }
+            // The segment is replayed, or its remaining records are torn.
+            self.pop_oldest()?;
+            self.commit(Cursor {
+                segment: self.segments[0].id,
+                offset: 0,
+            })?;
+        }
+    }
+
+    /// Move the cursor past a replayed record ending at `next`, unless its
+    /// segment was evicted while the record was replayed, which already moved
+    /// the cursor to the next segment.
+    fn advance(&mut self, next: Cursor) -> Result<()> {
+        if next.segment != self.cursor.segment {
+            return Ok(());
+        }
+        self.commit(next)
+    }
+
+    fn commit(&mut self, cursor: Cursor) -> Result<()> {
+        self.cursor = cursor;
+        let mut checkpoint = Vec::with_capacity(16);
+        checkpoint.extend_from_slice(&cursor.segment.to_le_bytes());
+        checkpoint.extend_from_slice(&cursor.offset.to_le_bytes());
+        fs::write(self.dir.join(CHECKPOINT_FILE), checkpoint).context(error::SpoolIoSnafu)
+    }
+
+    fn read_record(&self, segment: &Segment) -> io::Result<Option<(Vec<u8>, u64)>> {
+        let offset = self.cursor.offset;
+        if offset + HEADER_LEN > segment.size {
+            return Ok(None);
+        }
+
+        let mut file = File::open(segment_path(&self.dir, segment.id))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0; HEADER_LEN as usize];
+        file.read_exact(&mut header)?;
+        let end = offset + HEADER_LEN + u32::from_le_bytes(header) as u64;
+        if end > segment.size {
+            return Ok(None);
+        }
+        let mut data = vec![0; (end - offset - HEADER_LEN) as usize];
+        file.read_exact(&mut data)?;
+        Ok(Some((data, end)))
+    }
+
+    fn pop_oldest(&mut self) -> Result<Segment> {
+        let segment = self.segments.pop_front().unwrap();
+        fs::remove_file(segment_path(&self.dir, segment.id)).context(error::SpoolIoSnafu)?;
+        Ok(segment)
+    }
+
+    fn active_mut(&mut self) -> &mut Segment {
+        self.segments.back_mut().unwrap()
+    }
+
+    fn size(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.size).sum()
+    }
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{id:020}{SEGMENT_SUFFIX}"))
+}
+
+fn create_segment(dir: &Path, id: u64) -> io::Result<File> {
+    OpenOptions::new()
+        .append(true)
+        .create_new(true)
+        .open(segment_path(dir, id))
+}
+
+fn read_checkpoint(dir: &Path) -> io::Result<Option<Cursor>> {
+    let data = match fs::read(dir.join(CHECKPOINT_FILE)) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    // A torn checkpoint replays from the oldest segment.
+    let Ok(data) = <[u8; 16]>::try_from(data.as_slice()) else {
+        return Ok(None);
+    };
+    let (segment, offset) = data.split_at(8);
+    Ok(Some(Cursor {
+        segment: u64::from_le_bytes(segment.try_into().unwrap()),
+        offset: u64::from_le_bytes(offset.try_into().unwrap()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::v1::RowInsertRequest;
+    use crate::{ClientBuilder, Error, Hints};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "greptimedb-ingester-spool-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn requests(table_name: &str) -> RowInsertRequests {
+        RowInsertRequests {
+            inserts: vec![RowInsertRequest {
+                table_name: table_name.to_string(),
+                rows: None,
+            }],
+        }
+    }
+
+    fn append(log: &mut SpoolLog, table_name: &str) -> Result<()> {
+        log.append(&requests(table_name).encode_to_vec())
+    }
+
+    fn replay_all(log: &mut SpoolLog) -> Vec<String> {
+        let mut tables = Vec::new();
+        while let Some((requests, next)) = log.peek().unwrap() {
+            tables.push(requests.inserts[0].table_name.clone());
+            log.commit(next).unwrap();
+        }
+        tables
+    }
+
+    #[test]
+    fn test_replay_in_order_across_segments() {
+        let dir = temp_dir("order");
+        let options = SpoolOptions::new(&dir).segment_size(10);
+        let mut log = SpoolLog::open(&options).unwrap();
+        assert!(log.is_empty());
+
+        for table in ["a", "b", "c"] {
+            append(&mut log, table).unwrap();
+        }
+        assert_eq!(3, log.segments.len());
+        assert_eq!(3, log.metrics().spooled_requests);
+
+        let (_, next) = log.peek().unwrap().unwrap();
+        log.commit(next).unwrap();
+        drop(log);
+
+        // A new writer skips the replayed request.
+        let mut log = SpoolLog::open(&options).unwrap();
+        assert_eq!(vec!["b", "c"], replay_all(&mut log));
+        assert!(log.is_empty());
+        assert_eq!(1, log.segments.len());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_torn_record() {
+        let dir = temp_dir("torn");
+        let options = SpoolOptions::new(&dir);
+        let mut log = SpoolLog::open(&options).unwrap();
+        append(&mut log, "a").unwrap();
+        log.writer.write_all(&[100, 0, 0, 0, 1]).unwrap();
+        drop(log);
+
+        let mut log = SpoolLog::open(&options).unwrap();
+        assert_eq!(vec!["a"], replay_all(&mut log));
+        append(&mut log, "b").unwrap();
+        assert_eq!(vec!["b"], replay_all(&mut log));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_failed_append() {
+        let dir = temp_dir("failed-append");
+        let options = SpoolOptions::new(&dir);
+        let mut log = SpoolLog::open(&options).unwrap();
+        append(&mut log, "a").unwrap();
+
+        // A read only handle fails both the write and the truncation.
+        log.writer = File::open(segment_path(&dir, 0)).unwrap();
+        assert!(matches!(
+            append(&mut log, "b").unwrap_err(),
+            crate::Error::SpoolIo { .. }
+        ));
+        assert_eq!(2, log.segments.len());
+
+        append(&mut log, "c").unwrap();
+        assert_eq!(vec!["a", "c"], replay_all(&mut log));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let record = HEADER_LEN + requests("a").encoded_len() as u64;
+
+        let dir = temp_dir("drop-oldest");
+        let options = SpoolOptions::new(&dir)
+            .segment_size(record)
+            .max_size(2 * record);
+        let mut log = SpoolLog::open(&options).unwrap();
+        for table in ["a", "b", "c"] {
+            append(&mut log, table).unwrap();
+        }
+        assert_eq!(record, log.metrics().evicted_bytes);
+        assert_eq!(vec!["b", "c"], replay_all(&mut log));
+        let _ = fs::remove_dir_all(&dir);
+
+        let dir = temp_dir("reject-new");
+        let options = options.eviction(EvictionPolicy::RejectNew);
+        let options = SpoolOptions { dir, ..options };
+        let mut log = SpoolLog::open(&options).unwrap();
+        append(&mut log, "a").unwrap();
+        append(&mut log, "b").unwrap();
+        assert!(matches!(
+            append(&mut log, "c").unwrap_err(),
+            crate::Error::SpoolFull { .. }
+        ));
+        assert_eq!(1, log.metrics().rejected_requests);
+        assert_eq!(vec!["a", "b"], replay_all(&mut log));
+        let _ = fs::remove_dir_all(&options.dir);
+    }
+
+    #[test]
+    fn test_eviction_during_replay() {
+        let record = HEADER_LEN + requests("a").encoded_len() as u64;
+        let dir = temp_dir("evict-replaying");
+        let options = SpoolOptions::new(&dir)
+            .segment_size(record)
+            .max_size(2 * record);
+        let mut log = SpoolLog::open(&options).unwrap();
+        append(&mut log, "a").unwrap();
+        append(&mut log, "b").unwrap();
+
+        // "a" is evicted while it is being replayed.
+        let (_, next) = log.peek().unwrap().unwrap();
+        append(&mut log, "c").unwrap();
+        log.advance(next).unwrap();
+
+        assert_eq!(2 * record, log.metrics().pending_bytes);
+        assert_eq!(vec!["b", "c"], replay_all(&mut log));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_client_error_not_spooled() {
+        let dir = temp_dir("client-error");
+        let client = ClientBuilder::default().peers(["127.0.0.1:1"]).build();
+        let mut database = Database::new_with_dbname("public", client);
+        database.set_hints(Hints::new().set("ttl", "1d,2d"));
+        let writer = database.spooled_writer(SpoolOptions::new(&dir)).unwrap();
+
+        let err = writer.row_insert(requests("a")).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidHint { .. }), "{err:?}");
+        assert_eq!(0, writer.metrics().spooled_requests);
+
+        drop(writer);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::{self, Display};
+
+/// Status codes of GreptimeDB, carried by the errors of the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StatusCode {
+    // ====== Begin of common status code ==============
+    Success = 0,
+    Unknown = 1000,
+    Unsupported = 1001,
+    Unexpected = 1002,
+    Internal = 1003,
+    InvalidArguments = 1004,
+    Cancelled = 1005,
+    // ====== End of common status code ================
+
+    // ====== Begin of SQL related status code =========
+    InvalidSyntax = 2000,
+    // ====== End of SQL related status code ===========
+
+    // ====== Begin of query related status code =======
+    PlanQuery = 3000,
+    EngineExecuteQuery = 3001,
+    // ====== End of query related status code =========
+
+    // ====== Begin of catalog related status code =====
+    TableAlreadyExists = 4000,
+    TableNotFound = 4001,
+    TableColumnNotFound = 4002,
+    TableColumnExists = 4003,
+    DatabaseNotFound = 4004,
+    RegionNotFound = 4005,
+    RegionAlreadyExists = 4006,
+    RegionReadonly = 4007,
+    RegionNotReady = 4008,
+    RegionBusy = 4009,
+    // ====== End of catalog related status code =======
+
+    // ====== Begin of storage related status code =====
+    StorageUnavailable = 5000,
+    // ====== End of storage related status code =======
+
+    // ====== Begin of server related status code =====
+    RuntimeResourcesExhausted = 6000,
+    RateLimited = 6001,
+    // ====== End of server related status code =======
+
+    // ====== Begin of auth related status code =====
+    UserNotFound = 7000,
+    UnsupportedPasswordType = 7001,
+    UserPasswordMismatch = 7002,
+    AuthHeaderNotFound = 7003,
+    InvalidAuthHeader = 7004,
+    AccessDenied = 7005,
+    PermissionDenied = 7006,
+    // ====== End of auth related status code =====
+}
+
+impl StatusCode {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        use StatusCode::*;
+
+        let code = match value {
+            0 => Success,
+            1000 => Unknown,
+            1001 => Unsupported,
+            1002 => Unexpected,
+            1003 => Internal,
+            1004 => InvalidArguments,
+            1005 => Cancelled,
+            2000 => InvalidSyntax,
+            3000 => PlanQuery,
+            3001 => EngineExecuteQuery,
+            4000 => TableAlreadyExists,
+            4001 => TableNotFound,
+            4002 => TableColumnNotFound,
+            4003 => TableColumnExists,
+            4004 => DatabaseNotFound,
+            4005 => RegionNotFound,
+            4006 => RegionAlreadyExists,
+            4007 => RegionReadonly,
+            4008 => RegionNotReady,
+            4009 => RegionBusy,
+            5000 => StorageUnavailable,
+            6000 => RuntimeResourcesExhausted,
+            6001 => RateLimited,
+            7000 => UserNotFound,
+            7001 => UnsupportedPasswordType,
+            7002 => UserPasswordMismatch,
+            7003 => AuthHeaderNotFound,
+            7004 => InvalidAuthHeader,
+            7005 => AccessDenied,
+            7006 => PermissionDenied,
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    pub fn as_u32(&self) -> u32 {
+        *self as u32
+    }
+
+    /// Whether a request failed with this code may succeed if sent again.
+    pub fn is_retriable(&self) -> bool {
+        matches!(
+            self,
+            StatusCode::Internal
+                | StatusCode::RegionNotReady
+                | StatusCode::RegionBusy
+                | StatusCode::StorageUnavailable
+                | StatusCode::RuntimeResourcesExhausted
+                | StatusCode::RateLimited
+        )
+    }
+}
+
+impl Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The name of the variant, like `TableNotFound`.
+        write!(f, "{self:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_u32() {
+        for code in [
+            StatusCode::Success,
+            StatusCode::Cancelled,
+            StatusCode::TableNotFound,
+            StatusCode::RegionBusy,
+            StatusCode::RateLimited,
+            StatusCode::PermissionDenied,
+        ] {
+            assert_eq!(Some(code), StatusCode::from_u32(code.as_u32()));
+        }
+        assert_eq!(None, StatusCode::from_u32(1999));
+        assert_eq!("TableNotFound", StatusCode::TableNotFound.to_string());
+    }
+}
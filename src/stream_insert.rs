@@ -12,22 +12,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::client::DatabaseClient;
 use crate::error::Result;
-use crate::error::{self, IllegalDatabaseResponseSnafu};
+use crate::error::{self, IllegalDatabaseResponseSnafu, JoinTaskSnafu};
+use crate::metrics;
+use crate::trace;
 use greptime_proto::v1::greptime_request::Request;
-use greptime_proto::v1::{
-    greptime_database_client::GreptimeDatabaseClient, InsertRequest, RowInsertRequests,
-};
 use greptime_proto::v1::{
     greptime_response, AffectedRows, AuthHeader, GreptimeRequest, GreptimeResponse, InsertRequests,
     RequestHeader,
 };
-use snafu::OptionExt;
+use greptime_proto::v1::{InsertRequest, RowDeleteRequests, RowInsertRequests};
+use log::warn;
+use snafu::{OptionExt, ResultExt};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::metadata::{Ascii, MetadataValue};
-use tonic::transport::Channel;
+use tonic::metadata::MetadataMap;
 use tonic::{Response, Status};
 
 /// A structure that provides some methods for streaming data insert.
@@ -43,6 +45,10 @@ use tonic::{Response, Status};
 ///
 /// If you want to see a concrete usage example, please see
 /// [stream_inserter.rs](https://github.com/GreptimeTeam/greptimedb-client-rust/tree/master/examples/stream_ingest.rs).
+///
+/// A [`StreamInserter`] should be ended with either [`finish`](Self::finish)
+/// or [`abort`](Self::abort). Dropping it closes the stream as `finish` does,
+/// but the result is only logged as a warning.
 pub struct StreamInserter {
     sender: mpsc::Sender<GreptimeRequest>,
 
@@ -50,34 +56,52 @@ pub struct StreamInserter {
 
     dbname: String,
 
-    join: JoinHandle<std::result::Result<Response<GreptimeResponse>, Status>>,
+    peer: String,
+
+    start: Instant,
+
+    // Only `None` once the inserter is finished or aborted.
+    join: Option<JoinHandle<std::result::Result<Response<GreptimeResponse>, Status>>>,
 }
 
 impl StreamInserter {
     pub(crate) fn new(
-        mut client: GreptimeDatabaseClient<Channel>,
+        client: DatabaseClient,
         dbname: String,
         auth_header: Option<AuthHeader>,
         channel_size: usize,
-        hint: Option<MetadataValue<Ascii>>,
+        mut metadata: MetadataMap,
+        timeout: Option<Duration>,
     ) -> Result<StreamInserter> {
+        let DatabaseClient {
+            peer,
+            inner: mut client,
+        } = client;
+        // The server side of the stream continues the span of the caller.
+        trace::current_span().inject(&mut metadata);
         let (send, recv) = mpsc::channel(channel_size);
 
         let join: JoinHandle<std::result::Result<Response<GreptimeResponse>, Status>> =
             tokio::spawn(async move {
                 let recv_stream = ReceiverStream::new(recv);
                 let mut request = tonic::Request::new(recv_stream);
-                if let Some(hint) = hint {
-                    request.metadata_mut().insert("x-greptime-hints", hint);
-                }
-                client.handle_requests(request).await
+                *request.metadata_mut() = metadata;
+                let Some(timeout) = timeout else {
+                    return client.handle_requests(request).await;
+                };
+                request.set_timeout(timeout);
+                tokio::time::timeout(timeout, client.handle_requests(request))
+                    .await
+                    .unwrap_or_else(|_| Err(Status::deadline_exceeded("stream timed out")))
             });
 
         Ok(StreamInserter {
             sender: send,
             auth_header,
             dbname,
-            join,
+            peer,
+            start: Instant::now(),
+            join: Some(join),
         })
     }
 
@@ -86,30 +110,57 @@ impl StreamInserter {
         let inserts = InsertRequests { inserts: requests };
         let request = self.to_rpc_request(Request::Inserts(inserts));
 
-        self.sender.send(request).await.map_err(|e| {
-            error::ClientStreamingSnafu {
-                err_msg: e.to_string(),
-            }
-            .build()
-        })
+        self.send(request).await
     }
 
     /// Write Row based insert requests to GreptimeDB with streaming
     pub async fn row_insert(&self, requests: RowInsertRequests) -> Result<()> {
         let request = self.to_rpc_request(Request::RowInserts(requests));
 
-        self.sender.send(request).await.map_err(|e| {
-            error::ClientStreamingSnafu {
-                err_msg: e.to_string(),
-            }
-            .build()
-        })
+        self.send(request).await
     }
 
-    pub async fn finish(self) -> Result<u32> {
-        drop(self.sender);
+    /// Delete rows from GreptimeDB with streaming, the rows carry the primary
+    /// key and time index values of the rows to delete
+    pub async fn row_delete(&self, requests: RowDeleteRequests) -> Result<()> {
+        let request = self.to_rpc_request(Request::RowDeletes(requests));
 
-        let response = self.join.await.unwrap()?;
+        self.send(request).await
+    }
+
+    async fn send(&self, request: GreptimeRequest) -> Result<()> {
+        metrics::record_stream_request(&request);
+        let span = trace::stream_send_span(&request, &self.peer);
+        span.instrument(self.sender.send(request))
+            .await
+            .map_err(|e| {
+                error::ClientStreamingSnafu {
+                    err_msg: e.to_string(),
+                }
+                .build()
+            })?;
+        metrics::record_stream_channel_occupancy(
+            self.sender.max_capacity() - self.sender.capacity(),
+        );
+        Ok(())
+    }
+
+    /// Close the stream and get the total rows written by it.
+    pub async fn finish(mut self) -> Result<u32> {
+        let join = self.take_join();
+        let start = self.start;
+        // Dropping the sender closes the request stream.
+        drop(self);
+
+        let result = Self::wait(join).await;
+        metrics::record_stream_finished(start, &result);
+        result
+    }
+
+    async fn wait(
+        join: JoinHandle<std::result::Result<Response<GreptimeResponse>, Status>>,
+    ) -> Result<u32> {
+        let response = join.await.context(JoinTaskSnafu)??;
 
         let response = response
             .into_inner()
@@ -123,6 +174,18 @@ impl StreamInserter {
         Ok(value)
     }
 
+    /// Cancel the stream without waiting for the server. The requests not
+    /// handled by the server yet are discarded.
+    pub fn abort(mut self) {
+        self.take_join().abort();
+    }
+
+    fn take_join(&mut self) -> JoinHandle<std::result::Result<Response<GreptimeResponse>, Status>> {
+        self.join
+            .take()
+            .expect("join handle is only taken by finish or abort")
+    }
+
     fn to_rpc_request(&self, request: Request) -> GreptimeRequest {
         GreptimeRequest {
             header: Some(RequestHeader {
@@ -134,3 +197,117 @@ impl StreamInserter {
         }
     }
 }
+
+impl Drop for StreamInserter {
+    fn drop(&mut self) {
+        let Some(join) = self.join.take() else {
+            return;
+        };
+
+        warn!(
+            "StreamInserter of database {} is dropped without finish or abort",
+            self.dbname
+        );
+        // The stream is still closed by dropping the sender, report how it
+        // ends since nobody waits for it.
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let dbname = self.dbname.clone();
+            runtime.spawn(async move {
+                match join.await {
+                    Ok(Ok(response)) => match response.into_inner().response {
+                        Some(greptime_response::Response::AffectedRows(AffectedRows { value })) => {
+                            warn!("Dropped StreamInserter of database {dbname} wrote {value} rows")
+                        }
+                        None => warn!(
+                            "Dropped StreamInserter of database {dbname} got an empty response"
+                        ),
+                    },
+                    Ok(Err(status)) => {
+                        warn!("Dropped StreamInserter of database {dbname} failed: {status}")
+                    }
+                    Err(e) => warn!("Dropped StreamInserter of database {dbname} failed: {e}"),
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use greptime_proto::v1::greptime_database_client::GreptimeDatabaseClient;
+    use tonic::transport::Channel;
+
+    use super::*;
+    use crate::Error;
+
+    fn unreachable_client() -> DatabaseClient {
+        // Nothing listens on port 1, so the stream fails once it connects.
+        let channel = Channel::from_static("http://127.0.0.1:1").connect_lazy();
+        DatabaseClient {
+            peer: "127.0.0.1:1".to_string(),
+            inner: GreptimeDatabaseClient::new(channel),
+        }
+    }
+
+    fn unreachable_inserter() -> StreamInserter {
+        StreamInserter::new(
+            unreachable_client(),
+            "public".to_string(),
+            None,
+            1,
+            MetadataMap::new(),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_finish_error() {
+        assert!(matches!(
+            unreachable_inserter().finish().await.unwrap_err(),
+            Error::Server { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_finish_aborted() {
+        let inserter = unreachable_inserter();
+        // The stream task is cancelled before it is ever polled.
+        inserter.join.as_ref().unwrap().abort();
+        let err = inserter.finish().await.unwrap_err();
+        assert!(matches!(err, Error::JoinTask { .. }));
+        assert!(err.to_string().contains("cancelled"));
+
+        unreachable_inserter().abort();
+    }
+
+    #[test]
+    fn test_drop_unfinished() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        // How the stream ends is reported in the background, by a task that
+        // finishes with the stream.
+        runtime.block_on(async {
+            let inserter = unreachable_inserter();
+            let streams = runtime.metrics().num_alive_tasks();
+            drop(inserter);
+            assert_eq!(streams + 1, runtime.metrics().num_alive_tasks());
+            tokio::time::timeout(std::time::Duration::from_secs(5), async {
+                while runtime.metrics().num_alive_tasks() > 0 {
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap();
+        });
+
+        // Nothing is reported outside of a runtime.
+        let inserter = runtime.block_on(async { unreachable_inserter() });
+        let streams = runtime.metrics().num_alive_tasks();
+        drop(inserter);
+        assert_eq!(streams, runtime.metrics().num_alive_tasks());
+    }
+}
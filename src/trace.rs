@@ -0,0 +1,268 @@
+// Copyright 2023 Greptime Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Spans of the client, created through [`tracing`](https://docs.rs/tracing)
+//! when the `tracing` feature is enabled, and a no-op otherwise.
+//!
+//! When the spans are exported with `tracing-opentelemetry`, the context of
+//! the span of a request is sent to the server as a W3C `traceparent` header,
+//! so the traces of GreptimeDB continue those of the application.
+
+use std::future::Future;
+
+use tonic::metadata::MetadataMap;
+
+use crate::api::v1::GreptimeRequest;
+use crate::error::Result;
+
+#[cfg(feature = "tracing")]
+#[derive(Clone, Debug)]
+pub(crate) struct Span(::tracing::Span);
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone, Debug)]
+pub(crate) struct Span;
+
+/// Span of a unary request, including its retries.
+#[cfg(feature = "tracing")]
+pub(crate) fn handle_span(request: &GreptimeRequest) -> Span {
+    use ::tracing::field::Empty;
+
+    let span = ::tracing::info_span!(
+        "greptimedb.handle",
+        kind = crate::metrics::request_kind(request),
+        tables = Empty,
+        rows = Empty,
+        peer = Empty,
+        affected_rows = Empty,
+        error = Empty,
+    );
+    record_tables(&span, request);
+    Span(span)
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn handle_span(_request: &GreptimeRequest) -> Span {
+    Span
+}
+
+/// Span of a request sent on a stream to `peer`.
+#[cfg(feature = "tracing")]
+pub(crate) fn stream_send_span(request: &GreptimeRequest, peer: &str) -> Span {
+    use ::tracing::field::Empty;
+
+    let span = ::tracing::info_span!(
+        "greptimedb.stream_send",
+        kind = crate::metrics::request_kind(request),
+        tables = Empty,
+        rows = Empty,
+        peer,
+    );
+    record_tables(&span, request);
+    Span(span)
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn stream_send_span(_request: &GreptimeRequest, _peer: &str) -> Span {
+    Span
+}
+
+/// The span the caller is in.
+#[cfg(feature = "tracing")]
+pub(crate) fn current_span() -> Span {
+    Span(::tracing::Span::current())
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn current_span() -> Span {
+    Span
+}
+
+/// Span of getting the channel to `addr` from the pool.
+#[cfg(feature = "tracing")]
+pub(crate) fn get_channel_span(addr: &str) -> Span {
+    Span(::tracing::debug_span!("greptimedb.get_channel", addr))
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn get_channel_span(_addr: &str) -> Span {
+    Span
+}
+
+#[cfg(feature = "tracing")]
+impl Span {
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.0.in_scope(f)
+    }
+
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        ::tracing::Instrument::instrument(future, self.0.clone())
+    }
+
+    pub(crate) fn record_peer(&self, peer: &str) {
+        self.0.record("peer", peer);
+    }
+
+    pub(crate) fn record_result(&self, result: &Result<u32>) {
+        match result {
+            Ok(rows) => self.0.record("affected_rows", rows),
+            Err(e) => self.0.record("error", ::tracing::field::display(e)),
+        };
+    }
+
+    /// Add the `traceparent` header of this span to `metadata`. Nothing is
+    /// added if the span is not exported to OpenTelemetry.
+    pub(crate) fn inject(&self, metadata: &mut MetadataMap) {
+        use opentelemetry::propagation::TextMapPropagator;
+        use opentelemetry_sdk::propagation::TraceContextPropagator;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        TraceContextPropagator::new()
+            .inject_context(&self.0.context(), &mut MetadataInjector(metadata));
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    #[inline]
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        f()
+    }
+
+    #[inline]
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        future
+    }
+
+    #[inline]
+    pub(crate) fn record_peer(&self, _peer: &str) {}
+
+    #[inline]
+    pub(crate) fn record_result(&self, _result: &Result<u32>) {}
+
+    #[inline]
+    pub(crate) fn inject(&self, _metadata: &mut MetadataMap) {}
+}
+
+/// Record the tables and the number of rows of `request`, unless nobody
+/// listens to the span.
+#[cfg(feature = "tracing")]
+fn record_tables(span: &::tracing::Span, request: &GreptimeRequest) {
+    use crate::api::v1::greptime_request::Request;
+
+    if span.is_disabled() {
+        return;
+    }
+    let (tables, rows): (Vec<&str>, usize) = match &request.request {
+        Some(Request::Inserts(r)) => (
+            r.inserts.iter().map(|i| i.table_name.as_str()).collect(),
+            r.inserts.iter().map(|i| i.row_count as usize).sum(),
+        ),
+        Some(Request::Deletes(r)) => (
+            r.deletes.iter().map(|d| d.table_name.as_str()).collect(),
+            r.deletes.iter().map(|d| d.row_count as usize).sum(),
+        ),
+        Some(Request::RowInserts(r)) => (
+            r.inserts.iter().map(|i| i.table_name.as_str()).collect(),
+            r.inserts
+                .iter()
+                .map(|i| i.rows.as_ref().map_or(0, |rows| rows.rows.len()))
+                .sum(),
+        ),
+        Some(Request::RowDeletes(r)) => (
+            r.deletes.iter().map(|d| d.table_name.as_str()).collect(),
+            r.deletes
+                .iter()
+                .map(|d| d.rows.as_ref().map_or(0, |rows| rows.rows.len()))
+                .sum(),
+        ),
+        Some(Request::Query(_)) | Some(Request::Ddl(_)) | None => return,
+    };
+    span.record("tables", tables.join(","));
+    span.record("rows", rows);
+}
+
+#[cfg(feature = "tracing")]
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+#[cfg(feature = "tracing")]
+impl opentelemetry::propagation::Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        use tonic::metadata::{MetadataKey, MetadataValue};
+
+        if let (Ok(key), Ok(value)) = (
+            MetadataKey::from_bytes(key.as_bytes()),
+            MetadataValue::try_from(value),
+        ) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::api::v1::greptime_request::Request;
+    use crate::api::v1::{RowInsertRequest, RowInsertRequests, Rows};
+
+    fn row_inserts() -> GreptimeRequest {
+        let insert = |table_name: &str, rows| RowInsertRequest {
+            table_name: table_name.to_string(),
+            rows: Some(Rows {
+                schema: vec![],
+                rows: vec![Default::default(); rows],
+            }),
+        };
+        GreptimeRequest {
+            header: None,
+            request: Some(Request::RowInserts(RowInsertRequests {
+                inserts: vec![insert("cpu", 2), insert("mem", 3)],
+            })),
+        }
+    }
+
+    #[test]
+    fn test_inject() {
+        let tracer = TracerProvider::builder().build().tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        ::tracing::subscriber::with_default(subscriber, || {
+            let mut metadata = MetadataMap::new();
+            handle_span(&row_inserts()).inject(&mut metadata);
+            let traceparent = metadata.get("traceparent").unwrap().to_str().unwrap();
+            // version-trace_id-parent_id-flags
+            let parts: Vec<_> = traceparent.split('-').collect();
+            assert_eq!(4, parts.len());
+            assert_eq!("00", parts[0]);
+            assert_eq!(32, parts[1].len());
+            assert_eq!(16, parts[2].len());
+        });
+    }
+
+    #[test]
+    fn test_inject_without_opentelemetry() {
+        let mut metadata = MetadataMap::new();
+        handle_span(&row_inserts()).inject(&mut metadata);
+        assert!(metadata.is_empty());
+    }
+}